@@ -0,0 +1,105 @@
+use chip_8::display::filter;
+use chip_8::display::palette::Palette;
+use chip_8::display::phosphor;
+use chip_8::display::texture::FrameTexture;
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+use sdl2::surface::Surface;
+use std::time::{Duration, Instant};
+
+/*
+ * Render time per frame, per-pixel fill_rect against one streaming texture upload
+ *
+ * Uses SDL's software renderer on an off-screen surface so no window or GPU is needed,
+ * run with `cargo bench --bench render`
+ */
+const FRAMES: u32 = 300;
+const WINDOW_WIDTH: u32 = 1280;
+const WINDOW_HEIGHT: u32 = 640;
+
+/*
+ * A checkerboard with a spread of phosphor levels, lighting most of the screen
+ */
+fn levels(width: usize, height: usize, frame: u32) -> Vec<u8> {
+    (0..width * height)
+        .map(|i| {
+            let (x, y) = (i % width, i / width);
+            if (x + y + frame as usize).is_multiple_of(2) {
+                255
+            } else {
+                ((x * 7 + y * 13) % 256) as u8
+            }
+        })
+        .collect()
+}
+
+fn canvas(width: u32, height: u32) -> Canvas<Surface<'static>> {
+    let surface = Surface::new(WINDOW_WIDTH, WINDOW_HEIGHT, PixelFormatEnum::ARGB8888).unwrap();
+    let mut canvas = surface.into_canvas().unwrap();
+    canvas.set_logical_size(width, height).unwrap();
+    canvas
+}
+
+/*
+ * How Display::render drew before, a draw call per lit pixel
+ */
+fn fill_rect(width: usize, height: usize, palette: &Palette) -> Duration {
+    let mut canvas = canvas(width as u32, height as u32);
+    let start = Instant::now();
+    for frame in 0..FRAMES {
+        canvas.set_draw_color(palette.background());
+        canvas.clear();
+        for (i, level) in levels(width, height, frame).iter().enumerate() {
+            if *level > 0 {
+                canvas.set_draw_color(phosphor::shade(
+                    palette.background(),
+                    palette.foreground(),
+                    *level,
+                ));
+                canvas
+                    .fill_rect(Rect::new((i % width) as i32, (i / width) as i32, 1, 1))
+                    .unwrap();
+            }
+        }
+        canvas.present();
+    }
+    start.elapsed() / FRAMES
+}
+
+fn streaming_texture(width: usize, height: usize, palette: &Palette) -> Duration {
+    let mut canvas = canvas(width as u32, height as u32);
+    let texture_creator = canvas.texture_creator();
+    let mut texture = FrameTexture::new(&texture_creator);
+    let start = Instant::now();
+    for frame in 0..FRAMES {
+        canvas.set_draw_color(palette.background());
+        canvas.clear();
+        let colors: Vec<_> = levels(width, height, frame)
+            .iter()
+            .map(|level| phosphor::shade(palette.background(), palette.foreground(), *level))
+            .collect();
+        let image = filter::colorize(&colors, width, height);
+        canvas
+            .copy(texture.upload(&image).unwrap(), None, None)
+            .unwrap();
+        canvas.present();
+    }
+    start.elapsed() / FRAMES
+}
+
+fn main() {
+    let palette = Palette::default();
+    for (width, height) in [(64, 32), (128, 64)] {
+        let before = fill_rect(width, height, &palette);
+        let after = streaming_texture(width, height, &palette);
+        println!(
+            "{}x{}: fill_rect {:?}/frame, streaming texture {:?}/frame ({:.1}x)",
+            width,
+            height,
+            before,
+            after,
+            before.as_secs_f64() / after.as_secs_f64()
+        );
+    }
+}
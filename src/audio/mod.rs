@@ -1,46 +1,457 @@
+use crate::audio::queue::SoundQueue;
+use crate::audio::sampler::Sampler;
+use crate::chip8::SoundEdge;
+use crate::chip8::megachip::SampleCommand;
 use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
 use std::error::Error;
+use std::f32::consts::TAU;
+use std::str::FromStr;
+use std::sync::Arc;
 
-pub struct SquareWave {
+pub mod queue;
+pub mod sampler;
+
+pub const SAMPLE_RATE: i32 = 44100;
+const BUFFER_SAMPLES: u16 = 512;
+const QUEUE_CAPACITY: usize = 1024;
+
+/*
+ * The callback trails the emulator by this many frames of audio
+ * plus one device buffer, which absorbs jitter in when frames are produced
+ */
+const LATENCY_FRAMES: f64 = 2.0;
+/*
+ * Beyond this lag (e.g. after fast forward or a long stall) the callback jumps
+ * straight to the latency target instead of trying to catch up
+ */
+const RESYNC_FRAMES: f64 = 6.0;
+/*
+ * The callback speeds up or slows down its clock by at most this much to hold
+ * the latency target, far too small to hear as a change in pitch or duration
+ */
+const MAX_RATE_ADJUST: f64 = 0.005;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Waveform {
+    Square,
+    /// Duty cycle between 0 and 1, the fraction of the period spent high
+    Pulse(f32),
+    Triangle,
+    Sine,
+    Noise,
+}
+
+impl FromStr for Waveform {
+    type Err = String;
+
+    /*
+     * square, triangle, sine, noise or pulse:<duty> e.g. pulse:0.125
+     */
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "square" => Ok(Self::Square),
+            "triangle" => Ok(Self::Triangle),
+            "sine" => Ok(Self::Sine),
+            "noise" => Ok(Self::Noise),
+            other => match other.strip_prefix("pulse") {
+                Some("") => Ok(Self::Pulse(0.25)),
+                Some(duty) => duty
+                    .trim_start_matches(':')
+                    .parse::<f32>()
+                    .map(|d| Self::Pulse(d.clamp(0.01, 0.99)))
+                    .map_err(|e| format!("Invalid pulse width: {}", e)),
+                None => Err(format!("Unknown waveform: {}", s)),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BeeperConfig {
+    pub waveform: Waveform,
+    pub frequency: f32,
+    pub volume: f32,
+    /// Fade in time in milliseconds
+    pub attack: f32,
+    /// Fade out time in milliseconds
+    pub release: f32,
+}
+
+impl Default for BeeperConfig {
+    fn default() -> Self {
+        Self {
+            waveform: Waveform::Square,
+            frequency: 440.0,
+            volume: 0.25,
+            attack: 2.0,
+            release: 5.0,
+        }
+    }
+}
+
+/*
+ * The beeper waveform, independent of any audio device
+ * so the same samples can be played through SDL or written to a file
+ *
+ * The sound timer opens and closes a gate. Rather than cutting the wave mid-cycle,
+ * which clicks, the output ramps up over the attack time and down over the release time.
+ * Holding the beeper ramps it down the same way but leaves the gate as it is.
+ */
+#[derive(Debug, Clone)]
+pub struct Beeper {
+    config: BeeperConfig,
+    sample_rate: f32,
     phase_inc: f32,
     phase: f32,
-    volume: f32,
+    gate: bool,
+    held: bool,
+    envelope: f32,
+    noise: u16,
+    noise_level: f32,
 }
 
-impl AudioCallback for SquareWave {
+impl Beeper {
+    pub fn new(sample_rate: i32, config: BeeperConfig) -> Self {
+        let mut beeper = Self {
+            config,
+            sample_rate: sample_rate as f32,
+            phase_inc: 0.0,
+            phase: 0.0,
+            gate: false,
+            held: false,
+            envelope: 0.0,
+            noise: 0x7FFF,
+            noise_level: 1.0,
+        };
+        beeper.set_config(config);
+        beeper
+    }
+
+    pub fn config(&self) -> BeeperConfig {
+        self.config
+    }
+
+    pub fn set_config(&mut self, config: BeeperConfig) {
+        self.config = config;
+        self.phase_inc = config.frequency / self.sample_rate;
+    }
+
+    pub fn set_volume(&mut self, volume: f32) {
+        self.config.volume = volume.clamp(0.0, 1.0);
+    }
+
+    pub fn set_gate(&mut self, on: bool) {
+        self.gate = on;
+    }
+
+    /*
+     * Silence the beeper while the emulator is paused, it sounds again on release
+     * if the gate is still open
+     */
+    pub fn set_held(&mut self, held: bool) {
+        self.held = held;
+    }
+
+    /*
+     * Generate one emulated frame of `samples` samples, opening and closing the gate
+     * at the positions the sound timer changed during that frame
+     */
+    pub fn frame(&mut self, edges: &[SoundEdge], samples: usize, out: &mut Vec<f32>) {
+        let mut edges = edges.iter().peekable();
+        for i in 0..samples {
+            let position = i as f32 / samples as f32;
+            while let Some(edge) = edges.next_if(|edge| edge.position <= position) {
+                self.gate = edge.on;
+            }
+            out.push(self.sample());
+        }
+
+        if let Some(edge) = edges.last() {
+            self.gate = edge.on;
+        }
+    }
+
+    pub fn sample(&mut self) -> f32 {
+        self.step_envelope();
+        if self.envelope == 0.0 {
+            return 0.0;
+        }
+
+        let wave = match self.config.waveform {
+            Waveform::Square => {
+                if self.phase <= 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Waveform::Pulse(duty) => {
+                if self.phase < duty {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Waveform::Triangle => 1.0 - 4.0 * (self.phase - 0.5).abs(),
+            Waveform::Sine => (self.phase * TAU).sin(),
+            Waveform::Noise => self.noise_level,
+        };
+
+        let next = self.phase + self.phase_inc;
+        if self.config.waveform == Waveform::Noise
+            && (next >= 1.0 || (self.phase < 0.5) != (next < 0.5))
+        {
+            self.step_noise();
+        }
+        self.phase = next % 1.0;
+
+        wave * self.config.volume * self.envelope
+    }
+
+    fn step_envelope(&mut self) {
+        let (target, time) = if self.gate && !self.held {
+            (1.0, self.config.attack)
+        } else {
+            (0.0, self.config.release)
+        };
+
+        let samples = time / 1000.0 * self.sample_rate;
+        if samples < 1.0 {
+            self.envelope = target;
+            return;
+        }
+
+        let step = 1.0 / samples;
+        self.envelope = if self.envelope < target {
+            (self.envelope + step).min(target)
+        } else {
+            (self.envelope - step).max(target)
+        };
+    }
+
+    /*
+     * 15-bit LFSR, clocked twice per period so the noise has a pitch
+     */
+    fn step_noise(&mut self) {
+        let bit = (self.noise ^ (self.noise >> 1)) & 1;
+        self.noise = (self.noise >> 1) | (bit << 14);
+        self.noise_level = if self.noise & 1 == 1 { 1.0 } else { -1.0 };
+    }
+}
+
+/*
+ * Consumes the emulator's sound events
+ *
+ * `clock` is the callback's position on the emulated sample timeline. It advances by
+ * `rate` per output sample, where the rate is nudged to keep the clock a fixed latency
+ * behind what the emulator has produced, so the two never drift apart. When the
+ * emulator stalls the clock holds at the last produced sample.
+ */
+pub struct BeeperCallback {
+    beeper: Beeper,
+    sampler: Sampler,
+    queue: Arc<SoundQueue>,
+    clock: f64,
+    latency: f64,
+    resync: f64,
+    synced: bool,
+}
+
+impl AudioCallback for BeeperCallback {
     type Channel = f32;
     fn callback(&mut self, out: &mut [Self::Channel]) {
+        let produced = self.queue.produced() as f64;
+        let mut lag = produced - self.clock;
+
+        if !self.synced || lag > self.resync {
+            self.clock = (produced - self.latency).max(0.0);
+            self.synced = true;
+            lag = produced - self.clock;
+        }
+
+        let error = (lag - self.latency) / self.latency;
+        let rate = 1.0 + (error * MAX_RATE_ADJUST).clamp(-MAX_RATE_ADJUST, MAX_RATE_ADJUST);
+        let held = self.queue.is_held();
+
         for x in out.iter_mut() {
-            *x = if self.phase <= 0.5 {
-                self.volume
-            } else {
-                -self.volume
-            };
+            if self.clock < produced {
+                self.clock = (self.clock + rate).min(produced);
+            }
 
-            self.phase = (self.phase + self.phase_inc) % 1.0;
+            if let Some(on) = self.queue.pop_until(self.clock as u64) {
+                self.beeper.set_gate(on);
+            }
+            self.beeper.set_held(held);
+
+            //pausing holds the sample where it is
+            let sample = if held { 0.0 } else { self.sampler.sample() };
+            *x = (self.beeper.sample() + sample).clamp(-1.0, 1.0);
         }
     }
 }
 
+/*
+ * The device plays continuously, the emulator only feeds it sound timer events
+ */
 pub struct Audio {
-    pub device: AudioDevice<SquareWave>,
+    pub device: AudioDevice<BeeperCallback>,
+    config: BeeperConfig,
+    muted: bool,
+    queue: Arc<SoundQueue>,
+    samples_per_frame: f64,
+    frame: u64,
 }
 
 impl Audio {
-    pub fn new(sdl_context: &sdl2::Sdl) -> Result<Self, Box<dyn Error>> {
+    pub fn new(sdl_context: &sdl2::Sdl, config: BeeperConfig) -> Result<Self, Box<dyn Error>> {
         let audio_subsystem = sdl_context.audio()?;
 
         let desired_spec = AudioSpecDesired {
-            freq: Some(44100),
+            freq: Some(SAMPLE_RATE),
             channels: Some(1),
-            samples: None,
+            samples: Some(BUFFER_SAMPLES),
         };
 
-        let device = audio_subsystem.open_playback(None, &desired_spec, |spec| SquareWave {
-            phase_inc: 440.0 / spec.freq as f32,
-            phase: 0.0,
-            volume: 0.25,
+        let queue = Arc::new(SoundQueue::new(QUEUE_CAPACITY));
+        let device = audio_subsystem.open_playback(None, &desired_spec, |spec| {
+            let samples_per_frame = spec.freq as f64 / 60.0;
+            BeeperCallback {
+                beeper: Beeper::new(spec.freq, config),
+                sampler: Sampler::new(spec.freq, config.volume),
+                queue: queue.clone(),
+                clock: 0.0,
+                latency: samples_per_frame * LATENCY_FRAMES + spec.samples as f64,
+                resync: samples_per_frame * RESYNC_FRAMES + spec.samples as f64,
+                synced: false,
+            }
         })?;
-        Ok(Self { device })
+        let samples_per_frame = device.spec().freq as f64 / 60.0;
+        device.resume();
+
+        Ok(Self {
+            device,
+            config,
+            muted: false,
+            queue,
+            samples_per_frame,
+            frame: 0,
+        })
+    }
+
+    /*
+     * Called once per emulated frame with the sound timer changes from that frame.
+     * If the callback has fallen behind and the queue is full, the latest gate state
+     * is queued once it catches up, so a lost "off" never leaves the tone stuck on.
+     */
+    pub fn push_frame(&mut self, edges: &[SoundEdge]) {
+        self.queue.resync();
+        for edge in edges {
+            let time = (self.frame as f64 + edge.position as f64) * self.samples_per_frame;
+            self.queue.push_gate(time as u64, edge.on);
+        }
+
+        self.frame += 1;
+        self.queue
+            .set_produced((self.frame as f64 * self.samples_per_frame) as u64);
+    }
+
+    /*
+     * MegaChip sound started or stopped during a frame, mixed with the beeper.
+     * Samples start when the frame is handed over rather than at their exact position in it.
+     */
+    pub fn play_samples(&mut self, commands: &[SampleCommand]) {
+        if commands.is_empty() {
+            return;
+        }
+        let mut callback = self.device.lock();
+        for command in commands {
+            callback.sampler.command(command);
+        }
+    }
+
+    pub fn set_hold(&mut self, hold: bool) {
+        self.queue.set_hold(hold);
+    }
+
+    pub fn config(&self) -> BeeperConfig {
+        self.config
+    }
+
+    pub fn set_config(&mut self, config: BeeperConfig) {
+        self.config = config;
+        let volume = self.output_volume();
+        let mut callback = self.device.lock();
+        callback.beeper.set_config(config);
+        callback.beeper.set_volume(volume);
+        callback.sampler.set_volume(volume);
+    }
+
+    pub fn volume(&self) -> f32 {
+        self.config.volume
+    }
+
+    pub fn set_volume(&mut self, volume: f32) {
+        self.config.volume = volume.clamp(0.0, 1.0);
+        let volume = self.output_volume();
+        let mut callback = self.device.lock();
+        callback.beeper.set_volume(volume);
+        callback.sampler.set_volume(volume);
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.muted
+    }
+
+    pub fn toggle_mute(&mut self) -> bool {
+        self.muted = !self.muted;
+        let volume = self.output_volume();
+        let mut callback = self.device.lock();
+        callback.beeper.set_volume(volume);
+        callback.sampler.set_volume(volume);
+        drop(callback);
+        self.muted
+    }
+
+    /*
+     * The volume actually played, silent while muted
+     */
+    pub fn output_volume(&self) -> f32 {
+        if self.muted { 0.0 } else { self.config.volume }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resume_mid_beep() {
+        let config = BeeperConfig::default();
+        let queue = Arc::new(SoundQueue::new(QUEUE_CAPACITY));
+        let mut callback = BeeperCallback {
+            beeper: Beeper::new(SAMPLE_RATE, config),
+            sampler: Sampler::new(SAMPLE_RATE, config.volume),
+            queue: queue.clone(),
+            clock: 0.0,
+            latency: 1.0,
+            resync: f64::MAX,
+            synced: true,
+        };
+        let mut out = [0.0; 1024];
+        let sounding = |out: &[f32]| out[512..].iter().any(|sample| *sample != 0.0);
+
+        queue.push_gate(0, true);
+        queue.set_produced(1024);
+        callback.callback(&mut out);
+        assert!(sounding(&out));
+
+        //pausing silences the tone, resuming brings it back without a new edge
+        queue.set_hold(true);
+        callback.callback(&mut out);
+        assert!(!sounding(&out));
+        queue.set_hold(false);
+        queue.set_produced(4096);
+        callback.callback(&mut out);
+        assert!(sounding(&out));
     }
 }
@@ -0,0 +1,156 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+
+const ON_BIT: u64 = 1 << 63;
+//no sample time gets this far, so it marks an empty pending slot
+const NO_EVENT: u64 = u64::MAX;
+
+/*
+ * Single producer, single consumer queue of beeper events
+ *
+ * The emulator pushes "sound on/off at sample N" events measured on the emulated
+ * timeline and the audio callback pops them as its own sample clock passes N.
+ * Each event is packed into one AtomicU64 (bit 63 is the gate, the rest the sample
+ * time) so neither side ever takes a lock.
+ */
+pub struct SoundQueue {
+    slots: Vec<AtomicU64>,
+    head: AtomicUsize,
+    tail: AtomicUsize,
+    produced: AtomicU64,
+    hold: AtomicBool,
+    pending: AtomicU64,
+}
+
+impl SoundQueue {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            slots: (0..capacity.max(1)).map(|_| AtomicU64::new(0)).collect(),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            produced: AtomicU64::new(0),
+            hold: AtomicBool::new(false),
+            pending: AtomicU64::new(NO_EVENT),
+        }
+    }
+
+    /*
+     * Producer side, returns false and drops the event if the consumer has fallen too far behind
+     */
+    pub fn push(&self, time: u64, on: bool) -> bool {
+        self.push_value((time & !ON_BIT) | if on { ON_BIT } else { 0 })
+    }
+
+    fn push_value(&self, value: u64) -> bool {
+        let head = self.head.load(Ordering::Relaxed);
+        let tail = self.tail.load(Ordering::Acquire);
+        if head.wrapping_sub(tail) >= self.slots.len() {
+            return false;
+        }
+
+        self.slots[head % self.slots.len()].store(value, Ordering::Relaxed);
+        self.head.store(head.wrapping_add(1), Ordering::Release);
+        true
+    }
+
+    /*
+     * Producer side, like `push` but the latest event that did not fit is kept and queued
+     * by `resync` once there is room. Events in between are lost, the gate still ends up
+     * in the state it was last set to rather than stuck on.
+     */
+    pub fn push_gate(&self, time: u64, on: bool) {
+        let value = (time & !ON_BIT) | if on { ON_BIT } else { 0 };
+        if !self.resync() || !self.push_value(value) {
+            self.pending.store(value, Ordering::Relaxed);
+        }
+    }
+
+    /*
+     * Queues the kept event, false if there is still no room for it
+     */
+    pub fn resync(&self) -> bool {
+        let pending = self.pending.load(Ordering::Relaxed);
+        if pending == NO_EVENT || self.push_value(pending) {
+            self.pending.store(NO_EVENT, Ordering::Relaxed);
+            return true;
+        }
+        false
+    }
+
+    /*
+     * Consumer side, pops every event due at or before `time`
+     * and returns the gate state of the last one
+     */
+    pub fn pop_until(&self, time: u64) -> Option<bool> {
+        let mut state = None;
+        loop {
+            let tail = self.tail.load(Ordering::Relaxed);
+            let head = self.head.load(Ordering::Acquire);
+            if tail == head {
+                return state;
+            }
+
+            let value = self.slots[tail % self.slots.len()].load(Ordering::Relaxed);
+            if value & !ON_BIT > time {
+                return state;
+            }
+
+            state = Some(value & ON_BIT != 0);
+            self.tail.store(tail.wrapping_add(1), Ordering::Release);
+        }
+    }
+
+    /*
+     * The emulated sample clock at the end of the latest frame pushed
+     */
+    pub fn produced(&self) -> u64 {
+        self.produced.load(Ordering::Acquire)
+    }
+
+    pub fn set_produced(&self, time: u64) {
+        self.produced.store(time, Ordering::Release);
+    }
+
+    /*
+     * Silences the beeper regardless of queued events, used while the emulator is paused
+     */
+    pub fn is_held(&self) -> bool {
+        self.hold.load(Ordering::Relaxed)
+    }
+
+    pub fn set_hold(&self, hold: bool) {
+        self.hold.store(hold, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pop_until() {
+        let queue = SoundQueue::new(2);
+        assert!(queue.push(100, true));
+        assert!(queue.push(200, false));
+        assert!(!queue.push(300, true));
+
+        assert_eq!(queue.pop_until(50), None);
+        assert_eq!(queue.pop_until(150), Some(true));
+        assert!(queue.push(300, true));
+        assert_eq!(queue.pop_until(1000), Some(true));
+        assert_eq!(queue.pop_until(1000), None);
+    }
+
+    #[test]
+    fn test_full_queue_keeps_latest_gate() {
+        let queue = SoundQueue::new(1);
+        queue.push_gate(100, true);
+        queue.push_gate(200, false);
+        queue.push_gate(300, true);
+        queue.push_gate(400, false);
+
+        assert_eq!(queue.pop_until(1000), Some(true));
+        assert!(queue.resync());
+        assert_eq!(queue.pop_until(1000), Some(false));
+        assert_eq!(queue.pop_until(1000), None);
+    }
+}
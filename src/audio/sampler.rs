@@ -0,0 +1,101 @@
+use crate::chip8::megachip::{Sample, SampleCommand};
+use std::sync::Arc;
+
+/*
+ * Plays MegaChip's digitised sound
+ *
+ * 8-bit unsigned samples are resampled to the output rate by stepping through them
+ * at `rate / output rate` per output sample, without interpolation like the original.
+ */
+#[derive(Debug, Clone)]
+pub struct Sampler {
+    output_rate: f64,
+    data: Option<Arc<[u8]>>,
+    looping: bool,
+    position: f64,
+    step: f64,
+    volume: f32,
+}
+
+impl Sampler {
+    pub fn new(output_rate: i32, volume: f32) -> Self {
+        Self {
+            output_rate: output_rate as f64,
+            data: None,
+            looping: false,
+            position: 0.0,
+            step: 0.0,
+            volume,
+        }
+    }
+
+    pub fn set_volume(&mut self, volume: f32) {
+        self.volume = volume.clamp(0.0, 1.0);
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.data.is_some()
+    }
+
+    pub fn command(&mut self, command: &SampleCommand) {
+        match command {
+            SampleCommand::Play(sample) => self.play(sample),
+            SampleCommand::Stop => self.data = None,
+        }
+    }
+
+    /*
+     * A sample without data or with a rate of 0 would never move, it plays nothing
+     */
+    pub fn play(&mut self, sample: &Sample) {
+        self.data = (!sample.data.is_empty() && sample.rate > 0).then(|| sample.data.clone());
+        self.looping = sample.looping;
+        self.position = 0.0;
+        self.step = sample.rate as f64 / self.output_rate;
+    }
+
+    pub fn sample(&mut self) -> f32 {
+        let Some(data) = &self.data else {
+            return 0.0;
+        };
+
+        let value = (data[self.position as usize] as f32 - 128.0) / 128.0;
+        self.position += self.step;
+        if self.position >= data.len() as f64 {
+            if self.looping {
+                self.position %= data.len() as f64;
+            } else {
+                self.data = None;
+            }
+        }
+        value * self.volume
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_play_once() {
+        let mut sampler = Sampler::new(8000, 1.0);
+        sampler.play(&Sample {
+            rate: 4000,
+            data: Arc::from([255u8, 0]),
+            looping: false,
+        });
+
+        let played: Vec<f32> = (0..5).map(|_| sampler.sample()).collect();
+        assert_eq!(played[0], played[1]);
+        assert!(played[0] > 0.9 && played[2] < -0.9);
+        assert_eq!(played[4], 0.0);
+        assert!(!sampler.is_playing());
+
+        sampler.play(&Sample {
+            rate: 0,
+            data: Arc::from([255u8]),
+            looping: true,
+        });
+        assert!(!sampler.is_playing());
+    }
+}
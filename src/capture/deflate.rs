@@ -0,0 +1,182 @@
+/*
+ * Minimal zlib (RFC 1950) / DEFLATE (RFC 1951) compressor
+ *
+ * Uses greedy LZ77 matching over a single-entry hash table and the fixed Huffman
+ * tables, which is plenty for emulator frames made of long runs of identical pixels.
+ */
+const WINDOW_SIZE: usize = 32768;
+const HASH_BITS: usize = 15;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+struct BitWriter {
+    out: Vec<u8>,
+    buffer: u32,
+    count: u32,
+}
+
+impl BitWriter {
+    fn new() -> Self {
+        Self {
+            out: Vec::new(),
+            buffer: 0,
+            count: 0,
+        }
+    }
+
+    /*
+     * DEFLATE packs values starting from the least significant bit
+     */
+    fn bits(&mut self, value: u32, length: u32) {
+        self.buffer |= value << self.count;
+        self.count += length;
+        while self.count >= 8 {
+            self.out.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /*
+     * Huffman codes are stored most significant bit first
+     */
+    fn code(&mut self, code: u32, length: u32) {
+        let reversed = code.reverse_bits() >> (32 - length);
+        self.bits(reversed, length);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.out.push(self.buffer as u8);
+        }
+        self.out
+    }
+}
+
+fn literal(writer: &mut BitWriter, value: u16) {
+    match value {
+        0..=143 => writer.code(0x30 + value as u32, 8),
+        144..=255 => writer.code(0x190 + (value as u32 - 144), 9),
+        256..=279 => writer.code(value as u32 - 256, 7),
+        _ => writer.code(0xC0 + (value as u32 - 280), 8),
+    }
+}
+
+fn length(writer: &mut BitWriter, length: usize) {
+    let index = LENGTH_BASE
+        .iter()
+        .rposition(|&base| base as usize <= length)
+        .unwrap();
+    literal(writer, 257 + index as u16);
+    writer.bits(
+        (length - LENGTH_BASE[index] as usize) as u32,
+        LENGTH_EXTRA[index] as u32,
+    );
+}
+
+fn distance(writer: &mut BitWriter, distance: usize) {
+    let index = DISTANCE_BASE
+        .iter()
+        .rposition(|&base| base as usize <= distance)
+        .unwrap();
+    writer.code(index as u32, 5);
+    writer.bits(
+        (distance - DISTANCE_BASE[index] as usize) as u32,
+        DISTANCE_EXTRA[index] as u32,
+    );
+}
+
+fn hash(data: &[u8]) -> usize {
+    let value = (data[0] as u32) << 16 | (data[1] as u32) << 8 | data[2] as u32;
+    (value.wrapping_mul(2654435761) >> (32 - HASH_BITS)) as usize
+}
+
+/*
+ * Raw DEFLATE stream as a single fixed Huffman block
+ */
+pub fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter::new();
+    let mut table = vec![usize::MAX; 1 << HASH_BITS];
+
+    //BFINAL = 1, BTYPE = 01 (fixed Huffman)
+    writer.bits(1, 1);
+    writer.bits(1, 2);
+
+    let mut pos = 0;
+    while pos < data.len() {
+        let mut best = 0;
+        let mut candidate = usize::MAX;
+
+        if pos + MIN_MATCH <= data.len() {
+            let h = hash(&data[pos..]);
+            candidate = table[h];
+            table[h] = pos;
+
+            if candidate != usize::MAX && pos - candidate <= WINDOW_SIZE {
+                let limit = (data.len() - pos).min(MAX_MATCH);
+                while best < limit && data[candidate + best] == data[pos + best] {
+                    best += 1;
+                }
+            }
+        }
+
+        if best >= MIN_MATCH {
+            length(&mut writer, best);
+            distance(&mut writer, pos - candidate);
+
+            //keep the hash table warm for the bytes covered by the match
+            let end = (pos + best).min(data.len() + 1 - MIN_MATCH);
+            for p in pos + 1..end {
+                table[hash(&data[p..])] = p;
+            }
+            pos += best;
+        } else {
+            literal(&mut writer, data[pos] as u16);
+            pos += 1;
+        }
+    }
+
+    //end of block
+    literal(&mut writer, 256);
+    writer.finish()
+}
+
+pub fn adler32(data: &[u8]) -> u32 {
+    let mut a: u32 = 1;
+    let mut b: u32 = 0;
+    for chunk in data.chunks(5552) {
+        for byte in chunk {
+            a += *byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    b << 16 | a
+}
+
+/*
+ * zlib stream: header, DEFLATE data and the Adler-32 of the uncompressed data
+ */
+pub fn zlib(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    out.extend(deflate(data));
+    out.extend(adler32(data).to_be_bytes());
+    out
+}
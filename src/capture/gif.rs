@@ -0,0 +1,339 @@
+use crate::capture::Image;
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+const MAX_CODE: u16 = 4095;
+
+/*
+ * GIF delays are in hundredths of a second and most viewers play anything under 2 as 10
+ */
+const MIN_DELAY: u64 = 2;
+
+/*
+ * Animated GIF encoder
+ *
+ * Each frame carries its own colour table. Emulator frames rarely use more than a
+ * handful of colours so the table is exact; anything beyond 256 colours falls back
+ * to a fixed 3-3-2 palette. Frames of another size than the first, after a resolution
+ * switch, are scaled to fit the first.
+ *
+ * The clip keeps to real time on a clock of 2/100s ticks. A frame that would end on the
+ * same tick as the one before it is dropped, at 60 fps one in six, and the next frame
+ * shows it in its place if that frame is only a repeat.
+ */
+pub struct GifEncoder<W: Write> {
+    out: W,
+    width: u16,
+    height: u16,
+    frames: u64,
+    fps: u32,
+    /// Frames handed to the encoder, written or dropped
+    received: u64,
+    /// Hundredths of a second written so far
+    elapsed: u64,
+    dropped: Option<Image>,
+}
+
+impl<W: Write> GifEncoder<W> {
+    pub fn new(mut out: W, width: u16, height: u16, fps: u32) -> io::Result<Self> {
+        out.write_all(b"GIF89a")?;
+        out.write_all(&width.to_le_bytes())?;
+        out.write_all(&height.to_le_bytes())?;
+        //no global colour table, background index 0, square pixels
+        out.write_all(&[0x00, 0x00, 0x00])?;
+
+        //NETSCAPE2.0 application extension, loop forever
+        out.write_all(&[0x21, 0xFF, 0x0B])?;
+        out.write_all(b"NETSCAPE2.0")?;
+        out.write_all(&[0x03, 0x01, 0x00, 0x00, 0x00])?;
+
+        Ok(Self {
+            out,
+            width,
+            height,
+            frames: 0,
+            fps: fps.max(1),
+            received: 0,
+            elapsed: 0,
+            dropped: None,
+        })
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    pub fn add_frame(&mut self, image: &Image) -> io::Result<()> {
+        match self.next_delay() {
+            0 => self.dropped = Some(image.clone()),
+            delay => {
+                self.dropped = None;
+                self.write_frame(image, delay)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_frame(&mut self, image: &Image, delay: u16) -> io::Result<()> {
+        let fitted;
+        let image = if image.width != self.width as u32 || image.height != self.height as u32 {
+            fitted = image.fit(self.width as u32, self.height as u32);
+            &fitted
+        } else {
+            image
+        };
+
+        let (palette, indices) = index_colors(image);
+        let bits = (usize::BITS - (palette.len() - 1).leading_zeros()).max(1);
+        let min_code_size = bits.max(2) as u8;
+
+        //graphic control extension
+        self.out.write_all(&[0x21, 0xF9, 0x04, 0x00])?;
+        self.out.write_all(&delay.to_le_bytes())?;
+        self.out.write_all(&[0x00, 0x00])?;
+
+        //image descriptor with a local colour table of 2^bits entries
+        self.out.write_all(&[0x2C, 0x00, 0x00, 0x00, 0x00])?;
+        self.out.write_all(&self.width.to_le_bytes())?;
+        self.out.write_all(&self.height.to_le_bytes())?;
+        self.out.write_all(&[0x80 | (bits as u8 - 1)])?;
+
+        let mut table = vec![0u8; 3 << bits];
+        for (i, color) in palette.iter().enumerate() {
+            table[i * 3..i * 3 + 3].copy_from_slice(color);
+        }
+        self.out.write_all(&table)?;
+
+        self.out.write_all(&[min_code_size])?;
+        for block in lzw(&indices, min_code_size).chunks(255) {
+            self.out.write_all(&[block.len() as u8])?;
+            self.out.write_all(block)?;
+        }
+        self.out.write_all(&[0x00])?;
+
+        self.frames += 1;
+        Ok(())
+    }
+
+    /*
+     * Show the previous frame for one more frame, a single transparent pixel drawn over it
+     */
+    pub fn repeat_frame(&mut self) -> io::Result<()> {
+        if self.received == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "no frame to repeat",
+            ));
+        }
+
+        let delay = self.next_delay();
+        if delay == 0 {
+            return Ok(());
+        }
+        if let Some(image) = self.dropped.take() {
+            return self.write_frame(&image, delay);
+        }
+
+        //graphic control extension, leave the previous frame in place, index 0 transparent
+        self.out.write_all(&[0x21, 0xF9, 0x04, 0x05])?;
+        self.out.write_all(&delay.to_le_bytes())?;
+        self.out.write_all(&[0x00, 0x00])?;
+
+        //a 1x1 image with a two colour table
+        self.out
+            .write_all(&[0x2C, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x80])?;
+        self.out.write_all(&[0; 6])?;
+        self.out.write_all(&[2])?;
+        let data = lzw(&[0], 2);
+        self.out.write_all(&[data.len() as u8])?;
+        self.out.write_all(&data)?;
+        self.out.write_all(&[0x00])?;
+
+        self.frames += 1;
+        Ok(())
+    }
+
+    /*
+     * How long the next frame shows for, 0 when it ends on the tick the last one did
+     */
+    fn next_delay(&mut self) -> u16 {
+        self.received += 1;
+        let end = self.received * 100 / self.fps as u64 / MIN_DELAY * MIN_DELAY;
+        let delay = end - self.elapsed;
+        self.elapsed = end;
+        delay as u16
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        self.out.write_all(&[0x3B])?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+fn index_colors(image: &Image) -> (Vec<[u8; 3]>, Vec<u8>) {
+    let mut palette: Vec<[u8; 3]> = Vec::new();
+    let mut lookup: HashMap<[u8; 3], u8> = HashMap::new();
+    let mut indices = Vec::with_capacity(image.pixels.len() / 3);
+
+    for pixel in image.pixels.chunks(3) {
+        let color = [pixel[0], pixel[1], pixel[2]];
+        let index = match lookup.get(&color) {
+            Some(index) => *index,
+            None if palette.len() < 256 => {
+                let index = palette.len() as u8;
+                palette.push(color);
+                lookup.insert(color, index);
+                index
+            }
+            None => return quantize(image),
+        };
+        indices.push(index);
+    }
+
+    if palette.is_empty() {
+        palette.push([0; 3]);
+    }
+    (palette, indices)
+}
+
+fn quantize(image: &Image) -> (Vec<[u8; 3]>, Vec<u8>) {
+    let palette = (0..=255u8)
+        .map(|i| {
+            let r = (i >> 5) & 0x7;
+            let g = (i >> 2) & 0x7;
+            let b = i & 0x3;
+            [r * 255 / 7, g * 255 / 7, b * 255 / 3]
+        })
+        .collect();
+
+    let indices = image
+        .pixels
+        .chunks(3)
+        .map(|p| (p[0] & 0xE0) | ((p[1] & 0xE0) >> 3) | (p[2] >> 6))
+        .collect();
+    (palette, indices)
+}
+
+/*
+ * Variable width LZW as used by GIF, codes are packed least significant bit first
+ */
+fn lzw(indices: &[u8], min_code_size: u8) -> Vec<u8> {
+    let clear = 1u16 << min_code_size;
+    let end = clear + 1;
+
+    let mut out = Vec::new();
+    let mut buffer: u32 = 0;
+    let mut count: u32 = 0;
+    let mut emit = |code: u16, width: u32, out: &mut Vec<u8>| {
+        buffer |= (code as u32) << count;
+        count += width;
+        while count >= 8 {
+            out.push(buffer as u8);
+            buffer >>= 8;
+            count -= 8;
+        }
+    };
+
+    let mut dictionary: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next = end + 1;
+    let mut width = min_code_size as u32 + 1;
+
+    emit(clear, width, &mut out);
+
+    let mut iter = indices.iter();
+    let Some(first) = iter.next() else {
+        emit(end, width, &mut out);
+        if count > 0 {
+            out.push(buffer as u8);
+        }
+        return out;
+    };
+
+    let mut prefix = *first as u16;
+    for index in iter {
+        if let Some(code) = dictionary.get(&(prefix, *index)) {
+            prefix = *code;
+            continue;
+        }
+
+        emit(prefix, width, &mut out);
+
+        if next <= MAX_CODE {
+            dictionary.insert((prefix, *index), next);
+            if next == 1 << width && width < 12 {
+                width += 1;
+            }
+            next += 1;
+        } else {
+            emit(clear, width, &mut out);
+            dictionary.clear();
+            next = end + 1;
+            width = min_code_size as u32 + 1;
+        }
+
+        prefix = *index as u16;
+    }
+
+    emit(prefix, width, &mut out);
+    emit(end, width, &mut out);
+    if count > 0 {
+        out.push(buffer as u8);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sdl2::pixels::Color;
+
+    #[test]
+    fn test_frame_delay() {
+        let image = Image {
+            width: 1,
+            height: 1,
+            pixels: vec![0, 0, 0],
+        };
+        let mut gif = GifEncoder::new(Vec::new(), 1, 1, 60).unwrap();
+        for _ in 0..30 {
+            gif.add_frame(&image).unwrap();
+            gif.repeat_frame().unwrap();
+        }
+        assert_eq!(gif.frames(), 50);
+        let out = gif.finish().unwrap();
+
+        //a second of frames plays for a second
+        let delays: Vec<u16> = out
+            .windows(6)
+            .filter(|w| w[..3] == [0x21, 0xF9, 0x04])
+            .map(|w| u16::from_le_bytes([w[4], w[5]]))
+            .collect();
+        assert!(delays.iter().all(|delay| *delay == 2));
+        assert_eq!(delays.iter().sum::<u16>(), 100);
+    }
+
+    #[test]
+    fn test_resolution_switch() {
+        let lores = Image {
+            width: 2,
+            height: 1,
+            pixels: vec![255; 6],
+        };
+        let hires = Image::from_colors(&[Color::RGB(255, 255, 255); 8], 4, 2, 1);
+        let mut gif = GifEncoder::new(Vec::new(), 2, 1, 50).unwrap();
+        gif.add_frame(&lores).unwrap();
+        gif.add_frame(&hires).unwrap();
+        gif.add_frame(&lores).unwrap();
+        assert_eq!(gif.frames(), 3);
+
+        //every frame is drawn on the 2x1 canvas
+        let out = gif.finish().unwrap();
+        let sizes = out
+            .windows(10)
+            .filter(|w| w[..5] == [0x2C, 0x00, 0x00, 0x00, 0x00] && w[9] & 0x80 != 0)
+            .map(|w| (w[5], w[7]))
+            .collect::<Vec<_>>();
+        assert_eq!(sizes, [(2, 1); 3]);
+    }
+}
@@ -0,0 +1,161 @@
+use crate::chip8::framebuffer::FrameBuffer;
+use crate::display::palette::Palette;
+use crate::display::phosphor::Phosphor;
+use sdl2::pixels::Color;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub mod deflate;
+pub mod gif;
+pub mod png;
+pub mod recorder;
+pub mod wav;
+
+/*
+ * 8-bit RGB image used by every capture format
+ * these never touch SDL's video subsystem so they also work headless
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    /*
+     * One image pixel per CHIP-8 pixel, lit pixels are white on black
+     */
+    pub fn from_frame_buffer(frame_buffer: &FrameBuffer) -> Self {
+        let pixels = frame_buffer
+            .lit()
+            .flat_map(|pixel| if pixel { [255; 3] } else { [0; 3] })
+            .collect();
+
+        Self {
+            width: frame_buffer.width() as u32,
+            height: frame_buffer.height() as u32,
+            pixels,
+        }
+    }
+
+    /*
+     * The image as it appears in the window
+     * takes the shaded pixels `Display::render` uses and every
+     * CHIP-8 pixel becomes a `scale` x `scale` block
+     */
+    pub fn from_colors(colors: &[Color], width: u32, height: u32, scale: u32) -> Self {
+        let scale = scale.max(1) as usize;
+        let out_width = width as usize * scale;
+        let mut pixels = Vec::with_capacity(out_width * height as usize * scale * 3);
+
+        for row in colors.chunks(width as usize) {
+            let mut line = Vec::with_capacity(out_width * 3);
+            for shaded in row {
+                for _ in 0..scale {
+                    line.extend([shaded.r, shaded.g, shaded.b]);
+                }
+            }
+            for _ in 0..scale {
+                pixels.extend_from_slice(&line);
+            }
+        }
+
+        Self {
+            width: out_width as u32,
+            height: height * scale as u32,
+            pixels,
+        }
+    }
+
+    /*
+     * The image scaled to fit `width` x `height` keeping its aspect ratio,
+     * centred on black
+     */
+    pub fn fit(&self, width: u32, height: u32) -> Self {
+        let scale = (width as f32 / self.width as f32).min(height as f32 / self.height as f32);
+        let scaled_width = ((self.width as f32 * scale) as u32).clamp(1, width);
+        let scaled_height = ((self.height as f32 * scale) as u32).clamp(1, height);
+        let left = (width - scaled_width) / 2;
+        let top = (height - scaled_height) / 2;
+
+        let mut pixels = vec![0; (width * height * 3) as usize];
+        for y in 0..scaled_height {
+            let source_y = y * self.height / scaled_height;
+            for x in 0..scaled_width {
+                let source_x = x * self.width / scaled_width;
+                let from = ((source_y * self.width + source_x) * 3) as usize;
+                let to = (((top + y) * width + left + x) * 3) as usize;
+                pixels[to..to + 3].copy_from_slice(&self.pixels[from..from + 3]);
+            }
+        }
+
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn save_png(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, png::encode(self))
+    }
+}
+
+/*
+ * Write the raw frame buffer bitmap to a PNG
+ */
+pub fn screenshot(frame_buffer: &FrameBuffer, path: impl AsRef<Path>) -> io::Result<()> {
+    Image::from_frame_buffer(frame_buffer).save_png(path)
+}
+
+/*
+ * Write the coloured, fade-applied frame to a PNG, scaled up by `scale`
+ */
+pub fn screenshot_scaled(
+    phosphor: &Phosphor,
+    palette: &Palette,
+    scale: u32,
+    path: impl AsRef<Path>,
+) -> io::Result<()> {
+    Image::from_colors(
+        &phosphor.colors(palette),
+        phosphor.width() as u32,
+        phosphor.height() as u32,
+        scale,
+    )
+    .save_png(path)
+}
+
+/*
+ * `<prefix>-<unix time in milliseconds>.<extension>` in the working directory
+ */
+pub fn timestamped_path(prefix: &str, extension: &str) -> PathBuf {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    PathBuf::from(format!("{}-{}.{}", prefix, millis, extension))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checksums() {
+        assert_eq!(png::crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(deflate::adler32(b"Wikipedia"), 0x11E6_0398);
+    }
+
+    #[test]
+    fn test_scaled_size() {
+        let colors = vec![Palette::default().foreground(); 64 * 32];
+        let image = Image::from_colors(&colors, 64, 32, 4);
+        assert_eq!((image.width, image.height), (256, 128));
+        assert_eq!(image.pixels.len(), 256 * 128 * 3);
+        assert_eq!(&image.pixels[0..3], &[0, 255, 0]);
+    }
+}
@@ -0,0 +1,54 @@
+use crate::capture::Image;
+use crate::capture::deflate;
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+
+/*
+ * Encode an RGB image as an 8-bit truecolour PNG
+ *
+ * Every scanline uses the Up filter, so rows repeated from the emulator's
+ * scaled pixels compress down to almost nothing.
+ */
+pub fn encode(image: &Image) -> Vec<u8> {
+    let mut out = SIGNATURE.to_vec();
+
+    let mut header = Vec::with_capacity(13);
+    header.extend(image.width.to_be_bytes());
+    header.extend(image.height.to_be_bytes());
+    //bit depth 8, colour type 2 (RGB), default compression, filter and no interlace
+    header.extend([8, 2, 0, 0, 0]);
+    chunk(&mut out, b"IHDR", &header);
+
+    let stride = image.width as usize * 3;
+    let mut raw = Vec::with_capacity((stride + 1) * image.height as usize);
+    let mut previous = vec![0u8; stride];
+    for row in image.pixels.chunks(stride) {
+        raw.push(2);
+        raw.extend(row.iter().zip(&previous).map(|(a, b)| a.wrapping_sub(*b)));
+        previous.copy_from_slice(row);
+    }
+    chunk(&mut out, b"IDAT", &deflate::zlib(&raw));
+    chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+fn chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend((data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend(kind);
+    out.extend(data);
+    let crc = crc32(&out[start..]);
+    out.extend(crc.to_be_bytes());
+}
+
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
@@ -0,0 +1,230 @@
+use crate::audio::sampler::Sampler;
+use crate::audio::{self, Beeper, BeeperConfig};
+use crate::capture::Image;
+use crate::capture::gif::GifEncoder;
+use crate::capture::png;
+use crate::capture::wav::WavWriter;
+use crate::chip8::SoundEdge;
+use crate::chip8::megachip::SampleCommand;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+const FRAME_RATE: u32 = 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordFormat {
+    Gif,
+    PngSequence,
+    PpmSequence,
+}
+
+impl RecordFormat {
+    /*
+     * Pick a format from the output path, a `.gif` file or a directory of numbered frames
+     */
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(e) if e.eq_ignore_ascii_case("gif") => Self::Gif,
+            Some(e) if e.eq_ignore_ascii_case("ppm") => Self::PpmSequence,
+            _ => Self::PngSequence,
+        }
+    }
+}
+
+/*
+ * Captures every rendered frame
+ *
+ * GIF recordings are written to a single file that plays in real time, sequences are written
+ * as `frame-000000.png` / `.ppm` files inside the target directory
+ */
+pub struct Recorder {
+    format: RecordFormat,
+    path: PathBuf,
+    gif: Option<GifEncoder<BufWriter<File>>>,
+    frames: u64,
+}
+
+impl Recorder {
+    pub fn new(format: RecordFormat, path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        if format != RecordFormat::Gif {
+            fs::create_dir_all(&path)?;
+        }
+
+        Ok(Self {
+            format,
+            path,
+            gif: None,
+            frames: 0,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    pub fn capture(&mut self, image: &Image) -> io::Result<()> {
+        match self.format {
+            RecordFormat::Gif => {
+                if self.gif.is_none() {
+                    let file = BufWriter::new(File::create(&self.path)?);
+                    self.gif = Some(GifEncoder::new(
+                        file,
+                        image.width as u16,
+                        image.height as u16,
+                        FRAME_RATE,
+                    )?);
+                }
+                if let Some(gif) = self.gif.as_mut() {
+                    gif.add_frame(image)?;
+                }
+            }
+            RecordFormat::PngSequence => {
+                fs::write(self.frame_path("png"), png::encode(image))?;
+            }
+            RecordFormat::PpmSequence => {
+                fs::write(self.frame_path("ppm"), ppm(image))?;
+            }
+        }
+
+        self.frames += 1;
+        Ok(())
+    }
+
+    /*
+     * Record the previous frame again, without building a new image
+     */
+    pub fn repeat(&mut self) -> io::Result<()> {
+        match self.format {
+            RecordFormat::Gif => match self.gif.as_mut() {
+                Some(gif) => gif.repeat_frame()?,
+                None => return Err(no_frame()),
+            },
+            RecordFormat::PngSequence => self.repeat_file("png")?,
+            RecordFormat::PpmSequence => self.repeat_file("ppm")?,
+        }
+
+        self.frames += 1;
+        Ok(())
+    }
+
+    fn repeat_file(&self, extension: &str) -> io::Result<()> {
+        let previous = self.frames.checked_sub(1).ok_or_else(no_frame)?;
+        let previous = self
+            .path
+            .join(format!("frame-{:06}.{}", previous, extension));
+        fs::copy(previous, self.frame_path(extension))?;
+        Ok(())
+    }
+
+    /*
+     * Close the recording, returns the number of frames captured
+     */
+    pub fn finish(self) -> io::Result<u64> {
+        if let Some(gif) = self.gif {
+            gif.finish()?.flush()?;
+        }
+        Ok(self.frames)
+    }
+
+    fn frame_path(&self, extension: &str) -> PathBuf {
+        self.path
+            .join(format!("frame-{:06}.{}", self.frames, extension))
+    }
+}
+
+fn no_frame() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, "no frame to repeat")
+}
+
+/*
+ * Binary PPM (P6), the simplest format most image tools can read
+ */
+pub fn ppm(image: &Image) -> Vec<u8> {
+    let mut out = format!("P6\n{} {}\n255\n", image.width, image.height).into_bytes();
+    out.extend_from_slice(&image.pixels);
+    out
+}
+
+/*
+ * Records the beeper and MegaChip's samples to a WAV file, one emulated frame at a time
+ *
+ * Rather than tapping the audio device, the waveform is generated again from the
+ * sound timer edges on the emulated timeline. Frame `n` always ends on sample `n * rate / 60`, so the audio stays
+ * sample-aligned with a video recording of the same frames.
+ */
+pub struct AudioRecorder {
+    path: PathBuf,
+    beeper: Beeper,
+    sampler: Sampler,
+    wav: WavWriter<BufWriter<File>>,
+    sample_rate: u64,
+    frames: u64,
+    buffer: Vec<f32>,
+}
+
+impl AudioRecorder {
+    pub fn new(path: impl Into<PathBuf>, config: BeeperConfig) -> io::Result<Self> {
+        let path = path.into();
+        let wav = WavWriter::new(
+            BufWriter::new(File::create(&path)?),
+            audio::SAMPLE_RATE as u32,
+        )?;
+
+        Ok(Self {
+            path,
+            beeper: Beeper::new(audio::SAMPLE_RATE, config),
+            sampler: Sampler::new(audio::SAMPLE_RATE, config.volume),
+            wav,
+            sample_rate: audio::SAMPLE_RATE as u64,
+            frames: 0,
+            buffer: Vec::new(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    pub fn set_volume(&mut self, volume: f32) {
+        self.beeper.set_volume(volume);
+        self.sampler.set_volume(volume);
+    }
+
+    /*
+     * Append one frame of audio, the beeper is gated by the sound timer changes within the frame.
+     * Samples start with the frame like they do on the audio device.
+     */
+    pub fn frame(&mut self, edges: &[SoundEdge], commands: &[SampleCommand]) -> io::Result<()> {
+        let start = self.frames * self.sample_rate / FRAME_RATE as u64;
+        let end = (self.frames + 1) * self.sample_rate / FRAME_RATE as u64;
+
+        for command in commands {
+            self.sampler.command(command);
+        }
+        self.buffer.clear();
+        self.beeper
+            .frame(edges, (end - start) as usize, &mut self.buffer);
+        for sample in self.buffer.iter_mut() {
+            *sample = (*sample + self.sampler.sample()).clamp(-1.0, 1.0);
+        }
+        self.wav.write(&self.buffer)?;
+
+        self.frames += 1;
+        Ok(())
+    }
+
+    pub fn finish(self) -> io::Result<u64> {
+        self.wav.finish()?;
+        Ok(self.frames)
+    }
+}
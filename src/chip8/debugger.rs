@@ -1,3 +1,12 @@
+/*
+ * One level of the call stack, the 2nnn at `caller` and where its 00EE goes back to
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StackFrame {
+    pub caller: u16,
+    pub return_address: u16,
+}
+
 #[derive(Debug, Default)]
 pub struct Debugger {
     debug: Propagate,
@@ -55,3 +64,23 @@ impl Debugger {
         &self.debug
     }
 }
+
+/*
+ * The call stack, innermost call first, one line per level
+ */
+pub fn backtrace(frames: &[StackFrame]) -> String {
+    if frames.is_empty() {
+        return "  (empty stack)".to_string();
+    }
+    frames
+        .iter()
+        .enumerate()
+        .map(|(level, frame)| {
+            format!(
+                "  #{} called from {:04X}, returns to {:04X}",
+                level, frame.caller, frame.return_address
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
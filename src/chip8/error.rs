@@ -0,0 +1,35 @@
+use std::error::Error;
+use std::fmt;
+
+/*
+ * Something the program did that the real interpreter could not have survived
+ * `pc` is the address of the instruction, which is not executed
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MachineError {
+    /// 2nnn with every stack level in use
+    StackOverflow { pc: u16, depth: usize },
+    /// 00EE with nothing to return to
+    StackUnderflow { pc: u16 },
+}
+
+impl MachineError {
+    pub fn pc(&self) -> u16 {
+        match self {
+            Self::StackOverflow { pc, .. } | Self::StackUnderflow { pc } => *pc,
+        }
+    }
+}
+
+impl fmt::Display for MachineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::StackOverflow { pc, depth } => {
+                write!(f, "Stack overflow at {:04X}, {} levels deep", pc, depth)
+            }
+            Self::StackUnderflow { pc } => write!(f, "Stack underflow at {:04X}", pc),
+        }
+    }
+}
+
+impl Error for MachineError {}
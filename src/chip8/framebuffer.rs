@@ -0,0 +1,555 @@
+/*
+ * The CHIP-8's display memory
+ *
+ * Each pixel is a bit mask with one bit per plane, so a single buffer covers the
+ * original 64x32 display, hires modes and multi-plane variants. Every change grows
+ * the dirty region until a consumer takes it.
+ *
+ * Two optional layers add colour: a zone colour layer for CHIP-8X and direct
+ * 0xAARRGGBB colours for MegaChip, where the pixel values are palette indices.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameBuffer {
+    width: usize,
+    height: usize,
+    planes: usize,
+    pixels: Vec<u8>,
+    dirty: Option<Region>,
+    frame: u64,
+    erases: u64,
+    colors: Option<ColorLayer>,
+    direct: Option<Vec<u32>>,
+}
+
+/*
+ * A rectangle of pixels, in frame buffer coordinates
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Region {
+    fn union(self, other: Self) -> Self {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        let right = (self.x + self.width).max(other.x + other.width);
+        let bottom = (self.y + self.height).max(other.y + other.height);
+        Self {
+            x,
+            y,
+            width: right - x,
+            height: bottom - y,
+        }
+    }
+}
+
+/*
+ * Colour RAM of the VP-590 colour board, used by CHIP-8X
+ *
+ * The screen is divided into zones of `zone_width` x `zone_height` pixels that each
+ * have a foreground colour from 0 to 7, while one background colour from 0 to 3 covers
+ * the whole screen. Only the indices are kept here, the display turns them into colours.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColorLayer {
+    zone_width: usize,
+    zone_height: usize,
+    columns: usize,
+    rows: usize,
+    zones: Vec<u8>,
+    background: u8,
+}
+
+/*
+ * The board comes up red on blue
+ */
+const DEFAULT_ZONE_COLOR: u8 = 1;
+const BACKGROUNDS: u8 = 4;
+
+impl ColorLayer {
+    pub fn new(width: usize, height: usize, zone_width: usize, zone_height: usize) -> Self {
+        let columns = width.div_ceil(zone_width);
+        let rows = height.div_ceil(zone_height);
+        Self {
+            zone_width,
+            zone_height,
+            columns,
+            rows,
+            zones: vec![DEFAULT_ZONE_COLOR; columns * rows],
+            background: 0,
+        }
+    }
+
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn zone(&self, column: usize, row: usize) -> u8 {
+        self.zones[row * self.columns + column]
+    }
+
+    /*
+     * Foreground colour of the zone a pixel is in
+     */
+    pub fn foreground(&self, x: usize, y: usize) -> u8 {
+        self.zone(x / self.zone_width, y / self.zone_height)
+    }
+
+    pub fn background(&self) -> u8 {
+        self.background
+    }
+}
+
+impl Default for FrameBuffer {
+    fn default() -> Self {
+        Self::new(64, 32, 1)
+    }
+}
+
+impl FrameBuffer {
+    pub fn new(width: usize, height: usize, planes: usize) -> Self {
+        assert!((1..=8).contains(&planes), "1 to 8 planes are supported");
+        Self {
+            width,
+            height,
+            planes,
+            pixels: vec![0; width * height],
+            dirty: None,
+            frame: 0,
+            erases: 0,
+            colors: None,
+            direct: None,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn planes(&self) -> usize {
+        self.planes
+    }
+
+    /*
+     * Change the resolution, e.g. when switching between lores and hires, which clears the screen
+     */
+    pub fn resize(&mut self, width: usize, height: usize) {
+        self.width = width;
+        self.height = height;
+        self.pixels = vec![0; width * height];
+        if let Some(colors) = &self.colors {
+            self.colors = Some(ColorLayer::new(
+                width,
+                height,
+                colors.zone_width,
+                colors.zone_height,
+            ));
+        }
+        if self.direct.is_some() {
+            self.direct = Some(vec![0xFF00_0000; width * height]);
+        }
+        self.mark_all();
+    }
+
+    /*
+     * Add a colour layer with zones of `zone_width` x `zone_height` pixels
+     */
+    pub fn enable_colors(&mut self, zone_width: usize, zone_height: usize) {
+        self.colors = Some(ColorLayer::new(
+            self.width,
+            self.height,
+            zone_width,
+            zone_height,
+        ));
+        self.mark_all();
+    }
+
+    pub fn colors(&self) -> Option<&ColorLayer> {
+        self.colors.as_ref()
+    }
+
+    /*
+     * Set the foreground colour of a zone, ignored outside the screen or without a colour layer
+     */
+    pub fn set_zone_color(&mut self, column: usize, row: usize, color: u8) {
+        let Some(colors) = self.colors.as_mut() else {
+            return;
+        };
+        if column >= colors.columns || row >= colors.rows {
+            return;
+        }
+        let (x, y) = (column * colors.zone_width, row * colors.zone_height);
+        let (width, height) = (colors.zone_width, colors.zone_height);
+        colors.zones[row * colors.columns + column] = color & 7;
+        self.mark(x, y, width.min(self.width - x), height.min(self.height - y));
+    }
+
+    /*
+     * Direct colour, every pixel has its own colour instead of being shaded by the palette
+     */
+    pub fn enable_direct_colors(&mut self) {
+        self.direct = Some(vec![0xFF00_0000; self.width * self.height]);
+        self.mark_all();
+    }
+
+    pub fn disable_direct_colors(&mut self) {
+        self.direct = None;
+        self.mark_all();
+    }
+
+    pub fn direct_colors(&self) -> Option<&[u32]> {
+        self.direct.as_deref()
+    }
+
+    /*
+     * Replace the whole picture, `values` are the palette indices and `colors` what they look like
+     */
+    pub fn load_direct_colors(&mut self, values: &[u8], colors: &[u32]) {
+        self.pixels.copy_from_slice(values);
+        match self.direct.as_mut() {
+            Some(direct) => direct.copy_from_slice(colors),
+            None => self.direct = Some(colors.to_vec()),
+        }
+        self.mark_all();
+    }
+
+    /*
+     * Step the background through its four colours
+     */
+    pub fn next_background(&mut self) {
+        if let Some(colors) = self.colors.as_mut() {
+            colors.background = (colors.background + 1) % BACKGROUNDS;
+            self.mark_all();
+        }
+    }
+
+    fn index(&self, x: usize, y: usize) -> usize {
+        y * self.width + x
+    }
+
+    /*
+     * Lit in any plane
+     */
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        self.pixels[self.index(x, y)] != 0
+    }
+
+    /*
+     * The plane mask of a pixel, bit 0 is plane 1
+     */
+    pub fn value(&self, x: usize, y: usize) -> u8 {
+        self.pixels[self.index(x, y)]
+    }
+
+    pub fn get_plane(&self, plane: usize, x: usize, y: usize) -> bool {
+        self.value(x, y) >> plane & 1 == 1
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, on: bool) {
+        self.set_plane(0, x, y, on);
+    }
+
+    pub fn set_plane(&mut self, plane: usize, x: usize, y: usize, on: bool) {
+        let index = self.index(x, y);
+        let bit = 1 << plane;
+        let old = self.pixels[index];
+        self.pixels[index] = if on { old | bit } else { old & !bit };
+        if self.pixels[index] != old {
+            self.erases += !on as u64;
+            self.mark(x, y, 1, 1);
+        }
+    }
+
+    /*
+     * Flip a pixel in plane 1, returning true if it was lit (a collision)
+     */
+    pub fn xor(&mut self, x: usize, y: usize) -> bool {
+        self.xor_plane(0, x, y)
+    }
+
+    pub fn xor_plane(&mut self, plane: usize, x: usize, y: usize) -> bool {
+        let index = self.index(x, y);
+        let bit = 1 << plane;
+        let collision = self.pixels[index] & bit != 0;
+        self.pixels[index] ^= bit;
+        self.erases += collision as u64;
+        self.mark(x, y, 1, 1);
+        collision
+    }
+
+    pub fn clear(&mut self) {
+        self.erases += 1;
+        self.pixels.fill(0);
+        if let Some(direct) = self.direct.as_mut() {
+            direct.fill(0xFF00_0000);
+        }
+        self.mark_all();
+    }
+
+    /*
+     * Clear only the planes in `mask`
+     */
+    pub fn clear_planes(&mut self, mask: u8) {
+        self.erases += 1;
+        for pixel in self.pixels.iter_mut() {
+            *pixel &= !mask;
+        }
+        self.mark_all();
+    }
+
+    /*
+     * Scrolling moves the picture and fills the uncovered edge with unlit pixels
+     */
+    pub fn scroll_down(&mut self, rows: usize) {
+        let shift = rows.min(self.height) * self.width;
+        self.pixels.rotate_right(shift);
+        self.pixels[..shift].fill(0);
+        self.mark_all();
+    }
+
+    pub fn scroll_up(&mut self, rows: usize) {
+        let shift = rows.min(self.height) * self.width;
+        self.pixels.rotate_left(shift);
+        let len = self.pixels.len();
+        self.pixels[len - shift..].fill(0);
+        self.mark_all();
+    }
+
+    pub fn scroll_right(&mut self, columns: usize) {
+        let columns = columns.min(self.width);
+        for row in self.pixels.chunks_mut(self.width) {
+            row.rotate_right(columns);
+            row[..columns].fill(0);
+        }
+        self.mark_all();
+    }
+
+    pub fn scroll_left(&mut self, columns: usize) {
+        let columns = columns.min(self.width);
+        for row in self.pixels.chunks_mut(self.width) {
+            row.rotate_left(columns);
+            let len = row.len();
+            row[len - columns..].fill(0);
+        }
+        self.mark_all();
+    }
+
+    /*
+     * Every pixel, lit in any plane, row by row
+     */
+    pub fn lit(&self) -> impl Iterator<Item = bool> + '_ {
+        self.pixels.iter().map(|pixel| *pixel != 0)
+    }
+
+    /*
+     * Plane masks row by row
+     */
+    pub fn values(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    fn mark(&mut self, x: usize, y: usize, width: usize, height: usize) {
+        let region = Region {
+            x,
+            y,
+            width,
+            height,
+        };
+        self.dirty = Some(match self.dirty {
+            Some(dirty) => dirty.union(region),
+            None => region,
+        });
+    }
+
+    fn mark_all(&mut self) {
+        self.dirty = Some(Region {
+            x: 0,
+            y: 0,
+            width: self.width,
+            height: self.height,
+        });
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty.is_some()
+    }
+
+    pub fn dirty_region(&self) -> Option<Region> {
+        self.dirty
+    }
+
+    /*
+     * The region changed since the last call
+     */
+    pub fn take_dirty(&mut self) -> Option<Region> {
+        self.dirty.take()
+    }
+
+    /*
+     * Number of emulated frames this buffer has been displayed for
+     */
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn next_frame(&mut self) {
+        self.frame += 1;
+    }
+
+    /*
+     * Running count of pixels turned off by drawing and of clears, scrolling does not count
+     */
+    pub fn erases(&self) -> u64 {
+        self.erases
+    }
+
+    /*
+     * FNV-1a of the resolution, the pixels and any colour layers, the same across runs
+     * and platforms so it can be stored to compare frames later
+     */
+    pub fn hash(&self) -> u64 {
+        let mut hash: u64 = 0xCBF2_9CE4_8422_2325;
+        let header = [self.width as u64, self.height as u64, self.planes as u64];
+        for byte in header
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .chain(self.pixels.iter().copied())
+            .chain(self.colors.iter().flat_map(|colors| {
+                colors
+                    .zones
+                    .iter()
+                    .copied()
+                    .chain(std::iter::once(colors.background))
+            }))
+            .chain(
+                self.direct
+                    .iter()
+                    .flatten()
+                    .flat_map(|color| color.to_le_bytes()),
+            )
+        {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0000_0100_0000_01B3);
+        }
+        hash
+    }
+}
+
+/*
+ * The last complete picture, for presenting only whole frames
+ *
+ * Programs move sprites by erasing them and drawing them again, and a frame that ends
+ * between the two would show the sprite missing. A draw that turned nothing off
+ * completes a picture, which is kept until the next one; the vertical blank presents
+ * the latest. A program that keeps erasing without ever completing a picture has the
+ * frame buffer presented as it is once it has been incomplete for `MAX_INCOMPLETE_FRAMES`.
+ */
+#[derive(Debug, Clone)]
+pub struct Picture {
+    picture: FrameBuffer,
+    complete: bool,
+    incomplete_frames: u32,
+    presented: bool,
+}
+
+const MAX_INCOMPLETE_FRAMES: u32 = 2;
+
+impl Picture {
+    pub fn new(frame_buffer: &FrameBuffer) -> Self {
+        Self {
+            picture: frame_buffer.clone(),
+            complete: true,
+            incomplete_frames: 0,
+            presented: false,
+        }
+    }
+
+    /*
+     * After every draw or clear, `erased` when it turned pixels off
+     */
+    pub fn drawn(&mut self, frame_buffer: &FrameBuffer, erased: bool) {
+        self.complete = !erased;
+        if !erased {
+            self.picture = frame_buffer.clone();
+            self.presented = false;
+        }
+    }
+
+    pub fn vblank(&mut self, frame_buffer: &FrameBuffer) {
+        if self.complete {
+            self.incomplete_frames = 0;
+            return;
+        }
+
+        self.incomplete_frames += 1;
+        if self.incomplete_frames >= MAX_INCOMPLETE_FRAMES {
+            self.drawn(frame_buffer, false);
+            self.incomplete_frames = 0;
+        }
+    }
+
+    /*
+     * The picture to present, once per picture
+     */
+    pub fn take(&mut self) -> Option<&FrameBuffer> {
+        if self.presented {
+            return None;
+        }
+        self.presented = true;
+        Some(&self.picture)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_xor_and_dirty() {
+        let mut frame_buffer = FrameBuffer::default();
+        assert!(!frame_buffer.xor(3, 4));
+        assert!(!frame_buffer.xor(10, 2));
+        assert!(frame_buffer.xor(3, 4));
+
+        assert!(!frame_buffer.get(3, 4));
+        assert!(frame_buffer.get(10, 2));
+        assert_eq!(
+            frame_buffer.take_dirty(),
+            Some(Region {
+                x: 3,
+                y: 2,
+                width: 8,
+                height: 3
+            })
+        );
+        assert!(!frame_buffer.is_dirty());
+    }
+
+    #[test]
+    fn test_scroll_and_hash() {
+        let mut frame_buffer = FrameBuffer::new(128, 64, 2);
+        frame_buffer.set_plane(1, 0, 0, true);
+        let before = frame_buffer.hash();
+
+        frame_buffer.scroll_down(4);
+        frame_buffer.scroll_right(4);
+        assert_eq!(frame_buffer.value(4, 4), 0b10);
+        assert_ne!(frame_buffer.hash(), before);
+
+        frame_buffer.scroll_up(4);
+        frame_buffer.scroll_left(4);
+        assert_eq!(frame_buffer.hash(), before);
+    }
+}
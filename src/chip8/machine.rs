@@ -0,0 +1,120 @@
+/*
+ * Memory layout of the machine the interpreter ran on
+ *
+ * Each platform has its own defaults, which can be overridden for interpreters that
+ * put things elsewhere, e.g. the ETI-660 loading programs at 0x600 or interpreters
+ * keeping the font at 0x50.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MachineConfig {
+    /// Where the ROM is copied to
+    pub load_address: u16,
+    /// The first instruction executed
+    pub start_address: u16,
+    /// Where the 4x5 hex digits go, Fx29 points I into this
+    pub font_address: u16,
+    /// Bytes of RAM
+    pub memory_size: usize,
+    /// Levels of subroutine calls before 2nnn overflows
+    pub stack_depth: usize,
+}
+
+impl Default for MachineConfig {
+    fn default() -> Self {
+        Self {
+            load_address: 0x200,
+            start_address: 0x200,
+            font_address: 0x000,
+            memory_size: 4096,
+            //the VIP interpreter keeps 12 return addresses below V0-VF
+            stack_depth: 12,
+        }
+    }
+}
+
+/*
+ * The smallest and largest memory that can be configured, MegaChip's 24-bit I reaches 16MB
+ */
+const MIN_MEMORY: usize = 512;
+const MAX_MEMORY: usize = 0x100_0000;
+
+impl MachineConfig {
+    /*
+     * Everything has to fit in memory, the font and the program may not overlap
+     */
+    pub fn validate(&self, font_size: usize) -> Result<(), String> {
+        if !(MIN_MEMORY..=MAX_MEMORY).contains(&self.memory_size) {
+            return Err(format!(
+                "Memory size must be between {} and {} bytes",
+                MIN_MEMORY, MAX_MEMORY
+            ));
+        }
+
+        let font = self.font_address as usize..self.font_address as usize + font_size;
+        if font.end > self.memory_size {
+            return Err(format!(
+                "The font at 0x{:04X} does not fit in memory",
+                self.font_address
+            ));
+        }
+        if font.contains(&(self.load_address as usize)) {
+            return Err(format!(
+                "The font at 0x{:04X} overlaps the program at 0x{:04X}",
+                self.font_address, self.load_address
+            ));
+        }
+        if self.stack_depth == 0 {
+            return Err("The stack must have at least one level".to_string());
+        }
+        if self.load_address as usize >= self.memory_size
+            || self.start_address as usize >= self.memory_size
+        {
+            return Err("The program must start inside memory".to_string());
+        }
+        Ok(())
+    }
+}
+
+/*
+ * Decimal, or hexadecimal with a 0x prefix
+ */
+pub fn parse_number(s: &str) -> Result<usize, String> {
+    let s = s.trim();
+    let parsed = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    parsed.map_err(|_| format!("Invalid number: {}", s))
+}
+
+/*
+ * An address in the 64K the program counter can reach
+ */
+pub fn parse_address(s: &str) -> Result<u16, String> {
+    let address = parse_number(s)?;
+    u16::try_from(address).map_err(|_| format!("Address out of range: {}", s.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let eti660 = MachineConfig {
+            load_address: 0x600,
+            start_address: 0x600,
+            ..MachineConfig::default()
+        };
+        assert_eq!(eti660.validate(80), Ok(()));
+
+        let overlapping = MachineConfig {
+            font_address: 0x1E0,
+            ..MachineConfig::default()
+        };
+        assert!(overlapping.validate(80).is_err());
+        assert_eq!(parse_number("0x50"), Ok(0x50));
+        assert_eq!(parse_number("8192"), Ok(8192));
+        assert!(parse_address("0x10000").is_err());
+    }
+}
@@ -0,0 +1,335 @@
+use crate::chip8::framebuffer::FrameBuffer;
+use std::sync::Arc;
+
+pub const WIDTH: usize = 256;
+pub const HEIGHT: usize = 192;
+
+/*
+ * How a MegaChip sprite pixel combines with the one already on screen, set by 080n
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlendMode {
+    /// Drawn over the screen by the palette colour's own alpha
+    #[default]
+    Normal,
+    /// 25% of the sprite, 75% of the screen
+    Quarter,
+    /// Half and half
+    Half,
+    /// Colour channels added, saturating at white
+    Add,
+    /// Colour channels multiplied
+    Multiply,
+}
+
+impl BlendMode {
+    pub fn from_nibble(n: u8) -> Self {
+        match n {
+            1 => Self::Quarter,
+            2 => Self::Half,
+            3 => Self::Add,
+            4 => Self::Multiply,
+            _ => Self::Normal,
+        }
+    }
+
+    fn blend(self, screen: u32, sprite: u32) -> u32 {
+        let channel = |value: u32, shift: u32| (value >> shift & 0xFF) as f32;
+        let mix = |weight: f32| {
+            [16, 8, 0].iter().fold(0xFF00_0000, |out, shift| {
+                let value = channel(screen, *shift)
+                    + (channel(sprite, *shift) - channel(screen, *shift)) * weight;
+                out | (value.round() as u32) << shift
+            })
+        };
+
+        match self {
+            Self::Normal => mix(channel(sprite, 24) / 255.0),
+            Self::Quarter => mix(0.25),
+            Self::Half => mix(0.5),
+            Self::Add => [16, 8, 0].iter().fold(0xFF00_0000, |out, shift| {
+                let value = (channel(screen, *shift) + channel(sprite, *shift)).min(255.0);
+                out | (value as u32) << shift
+            }),
+            Self::Multiply => [16, 8, 0].iter().fold(0xFF00_0000, |out, shift| {
+                let value = channel(screen, *shift) * channel(sprite, *shift) / 255.0;
+                out | (value as u32) << shift
+            }),
+        }
+    }
+}
+
+/*
+ * A digitised sound started by 060n
+ * 8-bit unsigned samples at `rate` Hz
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sample {
+    pub rate: u16,
+    pub data: Arc<[u8]>,
+    pub looping: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SampleCommand {
+    Play(Sample),
+    Stop,
+}
+
+/*
+ * MegaChip8
+ *
+ * SCHIP with a 256x192 mode switched on by 0011. In that mode sprites are
+ * `sprite_width` x `sprite_height` bytes of palette indices read from memory, index 0
+ * is transparent, and they are blended onto an ARGB screen instead of XORed. Drawing
+ * goes to a back buffer that 00E0 shows and then clears, so a frame is only seen
+ * once it is complete.
+ *
+ * The indices are kept alongside the colours for collisions, a sprite pixel landing
+ * on the collision colour sets VF.
+ */
+#[derive(Debug, Clone)]
+pub struct MegaChip {
+    enabled: bool,
+    palette: [u32; 256],
+    sprite_width: usize,
+    sprite_height: usize,
+    alpha: u8,
+    blend: BlendMode,
+    collision_color: u8,
+    colors: Vec<u32>,
+    indices: Vec<u8>,
+}
+
+impl Default for MegaChip {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MegaChip {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            palette: [0xFF00_0000; 256],
+            sprite_width: 0,
+            sprite_height: 0,
+            alpha: 0xFF,
+            blend: BlendMode::Normal,
+            collision_color: 0,
+            colors: vec![0xFF00_0000; WIDTH * HEIGHT],
+            indices: vec![0; WIDTH * HEIGHT],
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /*
+     * 0011 and 0010, the frame buffer switches to 256x192 with direct colour or back to SCHIP lores
+     */
+    pub fn set_enabled(&mut self, enabled: bool, frame_buffer: &mut FrameBuffer) {
+        self.enabled = enabled;
+        self.clear();
+        if enabled {
+            frame_buffer.resize(WIDTH, HEIGHT);
+            frame_buffer.enable_direct_colors();
+        } else {
+            frame_buffer.disable_direct_colors();
+            frame_buffer.resize(64, 32);
+        }
+    }
+
+    /*
+     * 02nn, `count` ARGB colours from memory become palette entries 1 onwards
+     */
+    pub fn load_palette(&mut self, memory: &[u8], address: usize, count: usize) {
+        for i in 0..count.min(255) {
+            let start = address + i * 4;
+            let Some(bytes) = memory.get(start..start + 4) else {
+                break;
+            };
+            self.palette[i + 1] = u32::from_be_bytes(bytes.try_into().unwrap());
+        }
+    }
+
+    /*
+     * 03nn and 04nn, 0 means 256
+     */
+    pub fn set_sprite_width(&mut self, width: u8) {
+        self.sprite_width = if width == 0 { 256 } else { width as usize };
+    }
+
+    pub fn set_sprite_height(&mut self, height: u8) {
+        self.sprite_height = if height == 0 { 256 } else { height as usize };
+    }
+
+    pub fn set_alpha(&mut self, alpha: u8) {
+        self.alpha = alpha;
+    }
+
+    pub fn set_blend(&mut self, blend: BlendMode) {
+        self.blend = blend;
+    }
+
+    pub fn set_collision_color(&mut self, index: u8) {
+        self.collision_color = index;
+    }
+
+    /*
+     * Draw the sprite at `address` to the back buffer, clipped at the edges
+     * returns true if it touched the collision colour
+     */
+    pub fn draw(&mut self, memory: &[u8], address: usize, x: usize, y: usize) -> bool {
+        let mut collision = false;
+        for row in 0..self.sprite_height {
+            let py = y + row;
+            if py >= HEIGHT {
+                break;
+            }
+            for column in 0..self.sprite_width {
+                let px = x + column;
+                if px >= WIDTH {
+                    break;
+                }
+                let index = memory
+                    .get(address + row * self.sprite_width + column)
+                    .copied()
+                    .unwrap_or(0);
+                if index == 0 {
+                    continue;
+                }
+
+                let pixel = py * WIDTH + px;
+                if self.indices[pixel] == self.collision_color {
+                    collision = true;
+                }
+                self.indices[pixel] = index;
+                self.colors[pixel] = self
+                    .blend
+                    .blend(self.colors[pixel], self.palette[index as usize]);
+            }
+        }
+        collision
+    }
+
+    pub fn clear(&mut self) {
+        self.colors.fill(0xFF00_0000);
+        self.indices.fill(0);
+    }
+
+    /*
+     * 00E0 in MegaChip mode, the back buffer goes on screen faded by the screen alpha
+     */
+    pub fn present(&mut self, frame_buffer: &mut FrameBuffer) {
+        let alpha = self.alpha as u32;
+        let colors: Vec<u32> = self
+            .colors
+            .iter()
+            .map(|color| {
+                [16, 8, 0].iter().fold(0xFF00_0000, |out, shift| {
+                    out | ((color >> shift & 0xFF) * alpha / 255) << shift
+                })
+            })
+            .collect();
+        frame_buffer.load_direct_colors(&self.indices, &colors);
+        self.clear();
+    }
+
+    /*
+     * Scrolling in MegaChip mode moves the back buffer
+     */
+    pub fn scroll_down(&mut self, rows: usize) {
+        let shift = rows.min(HEIGHT) * WIDTH;
+        self.colors.rotate_right(shift);
+        self.colors[..shift].fill(0xFF00_0000);
+        self.indices.rotate_right(shift);
+        self.indices[..shift].fill(0);
+    }
+
+    pub fn scroll_up(&mut self, rows: usize) {
+        let shift = rows.min(HEIGHT) * WIDTH;
+        let len = self.colors.len();
+        self.colors.rotate_left(shift);
+        self.colors[len - shift..].fill(0xFF00_0000);
+        self.indices.rotate_left(shift);
+        self.indices[len - shift..].fill(0);
+    }
+
+    pub fn scroll_right(&mut self, columns: usize) {
+        let columns = columns.min(WIDTH);
+        for (colors, indices) in self
+            .colors
+            .chunks_mut(WIDTH)
+            .zip(self.indices.chunks_mut(WIDTH))
+        {
+            colors.rotate_right(columns);
+            colors[..columns].fill(0xFF00_0000);
+            indices.rotate_right(columns);
+            indices[..columns].fill(0);
+        }
+    }
+
+    pub fn scroll_left(&mut self, columns: usize) {
+        let columns = columns.min(WIDTH);
+        for (colors, indices) in self
+            .colors
+            .chunks_mut(WIDTH)
+            .zip(self.indices.chunks_mut(WIDTH))
+        {
+            colors.rotate_left(columns);
+            colors[WIDTH - columns..].fill(0xFF00_0000);
+            indices.rotate_left(columns);
+            indices[WIDTH - columns..].fill(0);
+        }
+    }
+
+    /*
+     * 060n, the header at `address` is the sample rate in 2 bytes and the length in 3,
+     * the samples follow from `address + 6`
+     */
+    pub fn sample(memory: &[u8], address: usize, looping: bool) -> Option<Sample> {
+        let header = memory.get(address..address + 6)?;
+        let rate = u16::from_be_bytes([header[0], header[1]]);
+        let length = u32::from_be_bytes([0, header[2], header[3], header[4]]) as usize;
+        let start = address + 6;
+        let data = memory.get(start..(start + length).min(memory.len()))?;
+        Some(Sample {
+            rate,
+            data: data.into(),
+            looping,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_draw_and_present() {
+        let mut megachip = MegaChip::new();
+        let mut frame_buffer = FrameBuffer::default();
+        megachip.set_enabled(true, &mut frame_buffer);
+
+        //palette entry 1 is opaque red, entry 2 half transparent blue
+        let memory = [0xFF, 0xFF, 0x00, 0x00, 0x80, 0x00, 0x00, 0xFF, 1, 0, 2, 1];
+        megachip.load_palette(&memory, 0, 2);
+        megachip.set_sprite_width(2);
+        megachip.set_sprite_height(2);
+        megachip.set_collision_color(1);
+
+        assert!(!megachip.draw(&memory, 8, 10, 10));
+        //the blue pixel of the second sprite lands on the first one's red pixel at (11, 11)
+        assert!(megachip.draw(&memory, 8, 11, 10));
+        megachip.present(&mut frame_buffer);
+
+        let colors = frame_buffer.direct_colors().unwrap();
+        assert_eq!(colors[10 * WIDTH + 10], 0xFFFF_0000);
+        assert_eq!(colors[11 * WIDTH + 10], 0xFF00_0080);
+        assert_eq!(colors[11 * WIDTH + 11], 0xFF7F_0080);
+        assert_eq!(colors[12 * WIDTH + 10], 0xFF00_0000);
+        assert_eq!(frame_buffer.value(11, 11), 2);
+    }
+}
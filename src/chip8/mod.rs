@@ -1,43 +1,85 @@
 use rand::random_range;
 
-use crate::chip8::debugger::Debugger;
+use crate::chip8::debugger::{Debugger, StackFrame};
+use crate::chip8::error::MachineError;
+use crate::chip8::font::Font;
+use crate::chip8::framebuffer::{FrameBuffer, Picture};
+use crate::chip8::machine::MachineConfig;
+use crate::chip8::megachip::{BlendMode, MegaChip, SampleCommand};
+use crate::chip8::platform::Platform;
+use crate::chip8::quirks::Quirks;
+use crate::chip8::timing::TimingModel;
+use crate::vip::{self, Vip};
 pub mod debugger;
+pub mod error;
+pub mod font;
+pub mod framebuffer;
+pub mod machine;
+pub mod megachip;
+pub mod platform;
+pub mod quirks;
+pub mod timing;
 
-const FONT_SET: [u8; 80] = [
-    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
-    0x20, 0x60, 0x20, 0x20, 0x70, // 1
-    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
-    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
-    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
-    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
-    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
-    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
-    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
-    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
-    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
-    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
-    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
-    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
-    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
-    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
-];
+/*
+ * The HP48's RPL user flags SCHIP saves registers to
+ */
+pub const RPL_FLAGS: usize = 8;
+
+/*
+ * The keypads a program can read, CHIP-8X adds a second VP-580 keypad
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Keypad {
+    #[default]
+    First,
+    Second,
+}
+
+/*
+ * The sound timer starting or stopping during a frame
+ * `position` is how far through the frame it happened, from 0.0 to 1.0
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SoundEdge {
+    pub position: f32,
+    pub on: bool,
+}
 
 pub struct CPU {
     register: Register,
-    stack: [u16; 64],
-    pub frame_buffer: [bool; 64 * 32],
-    memory: [u8; 4096],
+    stack: Vec<u16>,
+    pub frame_buffer: FrameBuffer,
+    memory: Vec<u8>,
     pub debug: debugger::Debugger,
     pub keypad: [bool; 16],
+    pub second_keypad: [bool; 16],
+    key_presses: [[bool; 16]; 2],
+    awaited_key: Option<u8>,
+    pub quirks: Quirks,
+    pub timing: TimingModel,
+    platform: Platform,
+    machine: MachineConfig,
+    font: Font,
+    output_port: u8,
+    delaying: bool,
+    rpl: [u8; RPL_FLAGS],
+    megachip: Option<Box<MegaChip>>,
+    sample_commands: Vec<SampleCommand>,
+    cycle_debt: u32,
+    waiting_for_vblank: bool,
+    vip: Option<Box<Vip>>,
+    sound_on: bool,
+    sound_edges: Vec<SoundEdge>,
+    draw_count: u64,
+    picture: Option<Picture>,
 }
 
 struct Register {
     v_registers: [u8; 16],
-    index_register: u16,
+    index_register: u32,
     pc: u16,
     delay_timer: u8,
     sound_timer: u8,
-    stack_pointer: u8,
 }
 
 impl Default for CPU {
@@ -48,41 +90,184 @@ impl Default for CPU {
 
 impl CPU {
     pub fn new() -> Self {
+        let machine = MachineConfig::default();
         let mut cpu = Self {
             register: Register {
                 v_registers: [0; 16],
                 index_register: 0,
-                pc: 0x200, //starting point 512 bytes are always loaded into memory
+                pc: machine.start_address,
                 delay_timer: 0,
                 sound_timer: 0,
-                stack_pointer: 0,
             },
-            stack: [0; 64],
-            frame_buffer: [false; 64 * 32],
-            memory: [0; 4096],
+            stack: Vec::with_capacity(machine.stack_depth),
+            frame_buffer: FrameBuffer::default(),
+            memory: vec![0; machine.memory_size],
             debug: Debugger::new(),
             keypad: [false; 16],
+            second_keypad: [false; 16],
+            key_presses: [[false; 16]; 2],
+            awaited_key: None,
+            quirks: Quirks::default(),
+            timing: TimingModel::default(),
+            platform: Platform::default(),
+            machine,
+            font: Font::default(),
+            output_port: 0,
+            delaying: false,
+            rpl: [0; RPL_FLAGS],
+            megachip: None,
+            sample_commands: Vec::new(),
+            cycle_debt: 0,
+            waiting_for_vblank: false,
+            vip: None,
+            sound_on: false,
+            sound_edges: Vec::new(),
+            draw_count: 0,
+            picture: None,
         };
-        cpu.memory[0..80].copy_from_slice(&FONT_SET);
+        cpu.load_font();
         cpu
     }
 
-    pub fn load_rom(&mut self, data: &[u8]) {
-        let start = self.register.pc as usize;
+    /*
+     * Run on an emulated COSMAC VIP instead, the interpreter in its RAM executes the
+     * CHIP-8 program while this CPU only relays the keypad, display and sound
+     */
+    pub fn attach_vip(&mut self, vip: Vip) {
+        self.vip = Some(Box::new(vip));
+    }
+
+    pub fn is_vip(&self) -> bool {
+        self.vip.is_some()
+    }
+
+    /*
+     * Switch to another CHIP-8 variant, before the ROM is loaded
+     * this sets the memory layout and the display the variant had
+     */
+    pub fn set_platform(&mut self, platform: Platform) {
+        self.platform = platform;
+        self.set_machine(platform.machine())
+            .expect("platform memory layouts are valid");
+
+        let (width, height) = platform.resolution();
+        self.frame_buffer = FrameBuffer::new(width, height, 1);
+        if platform == Platform::Chip8X {
+            //the VP-590 colours 8x4 pixel zones
+            self.frame_buffer.enable_colors(8, 4);
+        }
+        self.megachip = (platform == Platform::MegaChip).then(Box::default);
+    }
+
+    pub fn platform(&self) -> Platform {
+        self.platform
+    }
+
+    /*
+     * Change the memory layout, before the ROM is loaded
+     * memory is cleared, the font copied to its new place and the program counter reset
+     */
+    pub fn set_machine(&mut self, machine: MachineConfig) -> Result<(), String> {
+        machine.validate(self.font_size())?;
+        self.machine = machine;
+        self.memory = vec![0; machine.memory_size];
+        self.load_font();
+        self.register.pc = machine.start_address;
+        self.stack = Vec::with_capacity(machine.stack_depth);
+        Ok(())
+    }
+
+    pub fn machine(&self) -> MachineConfig {
+        self.machine
+    }
+
+    /*
+     * Replace the font, before the ROM is loaded
+     */
+    pub fn set_font(&mut self, font: Font) {
+        self.font = font;
+        self.load_font();
+    }
+
+    pub fn font(&self) -> &Font {
+        &self.font
+    }
+
+    /*
+     * The small font, followed by the big one on platforms with Fx30
+     */
+    fn font_size(&self) -> usize {
+        if self.platform.is_schip() {
+            font::SMALL_SIZE + font::BIG_SIZE
+        } else {
+            font::SMALL_SIZE
+        }
+    }
+
+    fn load_font(&mut self) {
+        let start = self.machine.font_address as usize;
+        let big = start + font::SMALL_SIZE;
+        self.memory[start..big].copy_from_slice(&self.font.small);
+        if self.platform.is_schip() {
+            self.memory[big..big + font::BIG_SIZE].copy_from_slice(&self.font.big);
+        }
+    }
+
+    /*
+     * Addresses past the end of memory wrap around to the start,
+     * like the address lines of a machine with only that much RAM
+     */
+    fn read_memory(&self, address: usize) -> u8 {
+        self.memory[address % self.memory.len()]
+    }
+
+    fn write_memory(&mut self, address: usize, value: u8) {
+        let len = self.memory.len();
+        self.memory[address % len] = value;
+    }
+
+    /*
+     * Copy the program to the load address, or into the VIP's RAM when one is attached
+     */
+    pub fn load_rom(&mut self, data: &[u8]) -> Result<(), String> {
+        if let Some(vip) = self.vip.as_mut() {
+            return vip
+                .load(vip::PROGRAM_ADDRESS, data)
+                .map_err(|e| e.to_string());
+        }
+
+        let start = self.machine.load_address as usize;
         let end = start + data.len();
 
-        if end > 4096 {
-            panic!("ROM is too large to load into memory")
+        if end > self.memory.len() {
+            return Err(format!(
+                "The ROM is {} bytes, only {} fit in memory at 0x{:04X}",
+                data.len(),
+                self.memory.len().saturating_sub(start),
+                start
+            ));
+        }
+        let font = self.machine.font_address as usize;
+        if start < font + self.font_size() && font < end {
+            return Err(format!(
+                "The ROM at 0x{:04X} would overwrite the font at 0x{:04X}",
+                start, font
+            ));
         }
 
         self.memory[start..end].copy_from_slice(data);
+        Ok(())
     }
 
-    pub fn run(&mut self) {
+    /*
+     * Fetch, decode and execute one instruction
+     * an instruction that fails is not executed and the program counter stays on it
+     */
+    pub fn run(&mut self) -> Result<(), MachineError> {
         //fetch
         let pc = self.register.pc as usize;
-        let first_byte = self.memory[pc] as u16;
-        let second_byte = self.memory[pc + 1] as u16;
+        let first_byte = self.read_memory(pc) as u16;
+        let second_byte = self.read_memory(pc + 1) as u16;
 
         let opcode = first_byte << 8 | second_byte;
 
@@ -91,17 +276,24 @@ impl CPU {
             first_byte,
             second_byte,
             opcode,
-            self.register.stack_pointer,
+            self.stack.len() as u8,
         );
 
         //decode & execute
-        self.execute(opcode);
+        let (draws, erases) = (self.draw_count, self.frame_buffer.erases());
+        self.execute(opcode)?;
+        if self.draw_count != draws
+            && let Some(picture) = self.picture.as_mut()
+        {
+            picture.drawn(&self.frame_buffer, self.frame_buffer.erases() != erases);
+        }
 
         //increment pc
         self.register.pc += 2;
+        Ok(())
     }
 
-    fn execute(&mut self, opcode: u16) {
+    fn execute(&mut self, opcode: u16) -> Result<(), MachineError> {
         let digit = (opcode & 0xF000) >> 12;
         let x = (opcode & 0x0F00) >> 8;
         let y = (opcode & 0x00F0) >> 4;
@@ -109,18 +301,27 @@ impl CPU {
         let nnn = opcode & 0x0FFF;
         let kk = (opcode & 0x00FF) as u8;
 
+        if self.execute_platform(opcode) {
+            return Ok(());
+        }
+
         /*
          * CHIP-8 Instructions
          */
         match (digit, x, y, n) {
             (0, 0, 0xE, 0) => {
                 //CLS
-                self.frame_buffer = [false; 64 * 32]
+                self.frame_buffer.clear();
+                self.draw_count += 1;
             }
             (0, 0, 0xE, 0xE) => {
                 //Return from subroutine
-                self.register.stack_pointer -= 1;
-                self.register.pc = self.stack[self.register.stack_pointer as usize];
+                let Some(caller) = self.stack.pop() else {
+                    return Err(MachineError::StackUnderflow {
+                        pc: self.register.pc,
+                    });
+                };
+                self.register.pc = caller;
             }
 
             (1, _, _, _) => {
@@ -131,9 +332,13 @@ impl CPU {
             (2, _, _, _) => {
                 //2nnn
                 //Call subroutine at nnn
-                let sp = self.register.stack_pointer;
-                self.stack[sp as usize] = self.register.pc;
-                self.register.stack_pointer += 1;
+                if self.stack.len() >= self.machine.stack_depth {
+                    return Err(MachineError::StackOverflow {
+                        pc: self.register.pc,
+                        depth: self.machine.stack_depth,
+                    });
+                }
+                self.stack.push(self.register.pc);
                 self.register.pc = nnn - 2;
             }
             (3, _, _, _) => {
@@ -298,7 +503,7 @@ impl CPU {
             (0xA, _, _, _) => {
                 //Annn
                 // I = nnn
-                self.register.index_register = nnn;
+                self.register.index_register = nnn as u32;
             }
             (0xB, _, _, _) => {
                 //Bnnn
@@ -320,13 +525,16 @@ impl CPU {
                 let vx = self.register.v_registers[x as usize];
                 let vy = self.register.v_registers[y as usize];
                 self.register.v_registers[0xF] = 0;
+                self.draw_count += 1;
+                self.waiting_for_vblank =
+                    self.quirks.display_wait || self.timing == TimingModel::Vip;
 
                 for row in 0..n {
                     /*
                      * Read the sprite data stored from 0x0 up to 0x200
                      */
-                    let addr = self.register.index_register + row;
-                    let pixels = self.memory[addr as usize];
+                    let addr = self.register.index_register + row as u32;
+                    let pixels = self.read_memory(addr as usize);
 
                     for col in 0..8 {
                         // get the exact coordinates by shifting the pixels all the way to the right and ANDING them by 1
@@ -334,17 +542,12 @@ impl CPU {
                         // grab the exact coords and flatten them into a 1D array by the size of the display in width
                         // then store it in the frame buffer
                         if (pixels >> (7 - col)) & 1 == 1 {
-                            let x = (vx as u16 + col) % 64;
-                            let y = (vy as u16 + row) % 32;
-
-                            let index = (x + (y * 64)) as usize;
+                            let x = (vx as usize + col as usize) % self.frame_buffer.width();
+                            let y = (vy as usize + row as usize) % self.frame_buffer.height();
 
                             //Chip 8's design XORS the values on to the screen
-                            if self.frame_buffer[index] {
-                                self.frame_buffer[index] = false;
+                            if self.frame_buffer.xor(x, y) {
                                 self.register.v_registers[0xF] = 1;
-                            } else {
-                                self.frame_buffer[index] = true;
                             }
                         }
                     }
@@ -355,7 +558,7 @@ impl CPU {
                 //Ex9E
                 // Skip next instruction if key with the value of Vx is pressed
                 let key = self.register.v_registers[x as usize];
-                if self.keypad[key as usize] {
+                if self.is_key_down(Keypad::First, key as usize) {
                     self.register.pc += 2;
                 }
             }
@@ -364,7 +567,7 @@ impl CPU {
                 //ExA1
                 // Skip next instruction if key with the value of Vx is not pressed
                 let key = self.register.v_registers[x as usize];
-                if !self.keypad[key as usize] {
+                if !self.is_key_down(Keypad::First, key as usize) {
                     self.register.pc += 2;
                 }
             }
@@ -378,17 +581,26 @@ impl CPU {
             (0xF, _, 0, 0xA) => {
                 //Fx0A
                 // Wait for a key press, store the value of the key in Vx
-                let mut pressed = false;
-                for (i, key) in self.keypad.iter().enumerate() {
-                    if *key {
-                        pressed = true;
-                        self.register.v_registers[x as usize] = i as u8;
-                        break;
+                // with the key release quirk the key also has to be let go again
+                let pressed = (0..16)
+                    .find(|&key| self.is_key_down(Keypad::First, key))
+                    .map(|key| key as u8);
+                let key = match (self.quirks.key_release, self.awaited_key) {
+                    (false, _) => pressed,
+                    (true, None) => {
+                        self.awaited_key = pressed;
+                        None
                     }
-                }
+                    (true, Some(key)) if !self.keypad[key as usize] => {
+                        self.awaited_key = None;
+                        Some(key)
+                    }
+                    (true, Some(_)) => None,
+                };
 
-                if !pressed {
-                    self.register.pc -= 2;
+                match key {
+                    Some(key) => self.register.v_registers[x as usize] = key,
+                    None => self.register.pc -= 2,
                 }
             }
             (0xF, _, 1, 5) => {
@@ -404,15 +616,15 @@ impl CPU {
             (0xF, _, 1, 0xE) => {
                 //Fx1E
                 // set I = I + Vx
-                let vx = self.register.v_registers[x as usize] as u16;
+                let vx = self.register.v_registers[x as usize] as u32;
                 self.register.index_register += vx;
             }
             (0xF, _, 2, 9) => {
                 //Fx29
                 // Set I = Location of sprite for digit Vx
-                // All font data is stored in the first 80 bytes of memory (Vx * 5)
-                let vx = self.register.v_registers[x as usize] as u16;
-                self.register.index_register = vx * 5;
+                // The font is 5 bytes per digit starting at the machine's font address
+                let vx = self.register.v_registers[x as usize] as u32;
+                self.register.index_register = self.machine.font_address as u32 + vx * 5;
             }
 
             (0xF, _, 3, 3) => {
@@ -423,9 +635,9 @@ impl CPU {
                 let b = (vx / 10) % 10;
                 let c = vx % 10;
 
-                self.memory[self.register.index_register as usize] = a;
-                self.memory[self.register.index_register as usize + 1] = b;
-                self.memory[self.register.index_register as usize + 2] = c;
+                self.write_memory(self.register.index_register as usize, a);
+                self.write_memory(self.register.index_register as usize + 1, b);
+                self.write_memory(self.register.index_register as usize + 2, c);
             }
 
             (0xF, _, 5, 5) => {
@@ -435,10 +647,10 @@ impl CPU {
                 for index in 0..=x {
                     let start_addr = self.register.index_register as usize;
                     let vx = self.register.v_registers[index as usize];
-                    self.memory[start_addr + index as usize] = vx;
+                    self.write_memory(start_addr + index as usize, vx);
                 }
                 let i = self.register.index_register;
-                self.register.index_register = i + x + 1;
+                self.register.index_register = i + x as u32 + 1;
             }
 
             (0xF, _, 6, 5) => {
@@ -447,17 +659,633 @@ impl CPU {
                 // I is then set to I + x + 1
                 for index in 0..=x {
                     let start_addr = self.register.index_register as usize;
-                    let value = self.memory[start_addr + index as usize];
+                    let value = self.read_memory(start_addr + index as usize);
                     self.register.v_registers[index as usize] = value;
                 }
                 let i = self.register.index_register;
-                self.register.index_register = i + x + 1;
+                self.register.index_register = i + x as u32 + 1;
             }
             (0, _, _, _) => {
                 //nop
             }
             _ => (),
         }
+        Ok(())
+    }
+
+    /*
+     * Instructions the selected platform adds or changes, true if the opcode was one of them
+     */
+    fn execute_platform(&mut self, opcode: u16) -> bool {
+        match self.platform {
+            Platform::Chip8 => false,
+            Platform::Hires => {
+                if opcode == 0x0230 {
+                    //0230
+                    // The hires patch's clear screen
+                    self.frame_buffer.clear();
+                    self.draw_count += 1;
+                    return true;
+                }
+                false
+            }
+            Platform::Chip8E => self.execute_chip8e(opcode),
+            Platform::Chip8X => self.execute_chip8x(opcode),
+            Platform::SuperChip => self.execute_schip(opcode),
+            Platform::MegaChip => self.execute_megachip(opcode) || self.execute_schip(opcode),
+        }
+    }
+
+    fn execute_schip(&mut self, opcode: u16) -> bool {
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+        let y = ((opcode & 0x00F0) >> 4) as usize;
+        let n = (opcode & 0x000F) as usize;
+        let pc = self.register.pc;
+
+        match opcode & 0xF000 {
+            0x0000 if opcode & 0xFFF0 == 0x00C0 => {
+                //00Cn
+                // Scroll down n rows
+                self.frame_buffer.scroll_down(n);
+                self.draw_count += 1;
+            }
+            0x0000 if opcode == 0x00FB => {
+                //00FB
+                // Scroll right 4 pixels
+                self.frame_buffer.scroll_right(4);
+                self.draw_count += 1;
+            }
+            0x0000 if opcode == 0x00FC => {
+                //00FC
+                // Scroll left 4 pixels
+                self.frame_buffer.scroll_left(4);
+                self.draw_count += 1;
+            }
+            0x0000 if opcode == 0x00FD => {
+                //00FD
+                // Exit the interpreter, the program stops here
+                self.register.pc = pc.wrapping_sub(2);
+            }
+            0x0000 if opcode == 0x00FE || opcode == 0x00FF => {
+                //00FE, 00FF
+                // Switch to 64x32 lores or 128x64 hires, which clears the screen
+                let (width, height) = if opcode == 0x00FF {
+                    (128, 64)
+                } else {
+                    (64, 32)
+                };
+                self.frame_buffer.resize(width, height);
+                self.draw_count += 1;
+            }
+            0xD000 => {
+                //Dxyn
+                // Like CHIP-8 but sprites are clipped at the edges instead of wrapping,
+                // and n = 0 draws a 16x16 sprite of 32 bytes
+                let vx = self.register.v_registers[x] as usize;
+                let vy = self.register.v_registers[y] as usize;
+                self.register.v_registers[0xF] = 0;
+                self.draw_count += 1;
+                self.waiting_for_vblank =
+                    self.quirks.display_wait || self.timing == TimingModel::Vip;
+
+                let (width, height) = (self.frame_buffer.width(), self.frame_buffer.height());
+                let (left, top) = (vx % width, vy % height);
+                let (columns, rows) = if n == 0 { (16, 16) } else { (8, n) };
+                let address = self.register.index_register as usize;
+
+                for row in 0..rows {
+                    let y = top + row;
+                    if y >= height {
+                        break;
+                    }
+                    let bits = if columns == 16 {
+                        (self.read_memory(address + row * 2) as u16) << 8
+                            | self.read_memory(address + row * 2 + 1) as u16
+                    } else {
+                        (self.read_memory(address + row) as u16) << 8
+                    };
+                    for column in 0..columns {
+                        let x = left + column;
+                        if x >= width {
+                            break;
+                        }
+                        if (bits >> (15 - column)) & 1 == 1 && self.frame_buffer.xor(x, y) {
+                            self.register.v_registers[0xF] = 1;
+                        }
+                    }
+                }
+            }
+            0xF000 if opcode & 0xFF == 0x30 => {
+                //Fx30
+                // Set I = location of the 8x10 sprite for digit Vx, the big font follows the small one
+                let vx = self.register.v_registers[x] as u32;
+                self.register.index_register =
+                    self.machine.font_address as u32 + font::SMALL_SIZE as u32 + vx * 10;
+            }
+            0xF000 if opcode & 0xFF == 0x75 => {
+                //Fx75
+                // Save V0 to Vx in the RPL user flags, x < 8
+                let count = (x + 1).min(RPL_FLAGS);
+                self.rpl[..count].copy_from_slice(&self.register.v_registers[..count]);
+            }
+            0xF000 if opcode & 0xFF == 0x85 => {
+                //Fx85
+                // Restore V0 to Vx from the RPL user flags, x < 8
+                let count = (x + 1).min(RPL_FLAGS);
+                self.register.v_registers[..count].copy_from_slice(&self.rpl[..count]);
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /*
+     * The MegaChip additions, and 00E0, Dxyn and scrolling while 256x192 mode is on
+     */
+    fn execute_megachip(&mut self, opcode: u16) -> bool {
+        let Some(megachip) = self.megachip.as_mut() else {
+            return false;
+        };
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+        let y = ((opcode & 0x00F0) >> 4) as usize;
+        let n = (opcode & 0x000F) as u8;
+        let nn = (opcode & 0x00FF) as u8;
+        let i = self.register.index_register as usize;
+
+        match opcode & 0xFF00 {
+            0x0000 if opcode == 0x0010 || opcode == 0x0011 => {
+                //0010, 0011
+                // MegaChip mode off and on
+                megachip.set_enabled(opcode == 0x0011, &mut self.frame_buffer);
+                self.draw_count += 1;
+            }
+            0x0100 => {
+                //01nn nnnn
+                // I = the 24-bit address made of nn and the next word
+                let pc = self.register.pc as usize;
+                let low = (self.read_memory(pc + 2) as u32) << 8 | self.read_memory(pc + 3) as u32;
+                self.register.index_register = (nn as u32) << 16 | low;
+                self.register.pc += 2;
+            }
+            0x0200 => {
+                //02nn
+                // Load nn colours from I into the palette
+                megachip.load_palette(&self.memory, i, nn as usize);
+            }
+            0x0300 => {
+                //03nn
+                // Sprite width
+                megachip.set_sprite_width(nn);
+            }
+            0x0400 => {
+                //04nn
+                // Sprite height
+                megachip.set_sprite_height(nn);
+            }
+            0x0500 => {
+                //05nn
+                // Screen alpha
+                megachip.set_alpha(nn);
+            }
+            0x0600 => {
+                //060n
+                // Play the digitised sound at I, n = 0 loops it
+                if let Some(sample) = MegaChip::sample(&self.memory, i, n == 0) {
+                    self.sample_commands.push(SampleCommand::Play(sample));
+                }
+            }
+            0x0700 => {
+                //0700
+                // Stop the sound
+                self.sample_commands.push(SampleCommand::Stop);
+            }
+            0x0800 => {
+                //080n
+                // Sprite blend mode
+                megachip.set_blend(BlendMode::from_nibble(n));
+            }
+            0x0900 => {
+                //09nn
+                // Collision colour index
+                megachip.set_collision_color(nn);
+            }
+            0x0000 if opcode & 0xFFF0 == 0x00B0 => {
+                //00Bn
+                // Scroll up n rows
+                if megachip.is_enabled() {
+                    megachip.scroll_up(n as usize);
+                } else {
+                    self.frame_buffer.scroll_up(n as usize);
+                    self.draw_count += 1;
+                }
+            }
+            _ if !megachip.is_enabled() => return false,
+            0x0000 if opcode == 0x00E0 => {
+                //00E0
+                // Show the finished frame and start a new one
+                megachip.present(&mut self.frame_buffer);
+                self.draw_count += 1;
+            }
+            0x0000 if opcode & 0xFFF0 == 0x00C0 => megachip.scroll_down(n as usize),
+            0x0000 if opcode == 0x00FB => megachip.scroll_right(4),
+            0x0000 if opcode == 0x00FC => megachip.scroll_left(4),
+            //SCHIP's resolution switches don't apply to the 256x192 screen
+            0x0000 if opcode == 0x00FE || opcode == 0x00FF => {}
+            _ if opcode & 0xF000 == 0xD000 => {
+                //Dxyn
+                // Draw the sprite at I to the back buffer, VF = hit the collision colour
+                let vx = self.register.v_registers[x] as usize;
+                let vy = self.register.v_registers[y] as usize;
+                let collision = megachip.draw(&self.memory, i, vx, vy);
+                self.register.v_registers[0xF] = collision as u8;
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /*
+     * Sampled sound started and stopped during the last frame, MegaChip only
+     */
+    pub fn sample_commands(&self) -> &[SampleCommand] {
+        &self.sample_commands
+    }
+
+    fn execute_chip8e(&mut self, opcode: u16) -> bool {
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+        let y = ((opcode & 0x00F0) >> 4) as usize;
+        let kk = opcode & 0x00FF;
+        let pc = self.register.pc;
+
+        match (opcode & 0xF000, opcode & 0x000F) {
+            _ if opcode == 0x00ED => {
+                //00ED
+                // Stop, the program ends here
+                self.register.pc = pc.wrapping_sub(2);
+            }
+            _ if opcode == 0x00F2 => {
+                //00F2
+                // No operation
+            }
+            _ if opcode == 0x0151 => {
+                //0151
+                // Wait until the delay timer reaches 0
+                if self.register.delay_timer > 0 {
+                    self.register.pc = pc.wrapping_sub(2);
+                }
+            }
+            _ if opcode == 0x0188 => {
+                //0188
+                // Skip the next instruction
+                self.register.pc = pc.wrapping_add(2);
+            }
+            (0x5000, 1) => {
+                //5xy1
+                // Skip next instruction if Vx > Vy
+                if self.register.v_registers[x] > self.register.v_registers[y] {
+                    self.register.pc = pc.wrapping_add(2);
+                }
+            }
+            (0x5000, 2) => {
+                //5xy2
+                // Store Vx to Vy in memory starting at I, I is set past the last one
+                let i = self.register.index_register as usize;
+                for (offset, register) in (x..=y).enumerate() {
+                    self.write_memory(i + offset, self.register.v_registers[register]);
+                }
+                self.register.index_register += (y + 1).saturating_sub(x) as u32;
+            }
+            (0x5000, 3) => {
+                //5xy3
+                // Load Vx to Vy from memory starting at I, I is set past the last one
+                let i = self.register.index_register as usize;
+                for (offset, register) in (x..=y).enumerate() {
+                    self.register.v_registers[register] = self.read_memory(i + offset);
+                }
+                self.register.index_register += (y + 1).saturating_sub(x) as u32;
+            }
+            _ if opcode & 0xFF00 == 0xBB00 => {
+                //BBkk
+                // Branch back kk bytes from this instruction
+                self.register.pc = pc.wrapping_sub(kk).wrapping_sub(2);
+            }
+            _ if opcode & 0xFF00 == 0xBF00 => {
+                //BFkk
+                // Branch forward kk bytes from this instruction
+                self.register.pc = pc.wrapping_add(kk).wrapping_sub(2);
+            }
+            (0xF000, _) => match kk {
+                0x03 => {
+                    //Fx03
+                    // Output Vx to port 3
+                    self.output_port = self.register.v_registers[x];
+                }
+                0x1B => {
+                    //Fx1B
+                    // Skip Vx bytes
+                    let vx = self.register.v_registers[x] as u16;
+                    self.register.pc = pc.wrapping_add(vx);
+                }
+                0x4F => {
+                    //Fx4F
+                    // Set the delay timer to Vx and wait until it reaches 0
+                    if !self.delaying {
+                        self.register.delay_timer = self.register.v_registers[x];
+                        self.delaying = true;
+                    }
+                    if self.register.delay_timer > 0 {
+                        self.register.pc = pc.wrapping_sub(2);
+                    } else {
+                        self.delaying = false;
+                    }
+                }
+                0xE3 | 0xE7 => {
+                    //FxE3, FxE7
+                    // Read input port 3 into Vx, nothing is connected so it reads 0
+                    self.register.v_registers[x] = 0;
+                }
+                _ => return false,
+            },
+            _ => return false,
+        }
+        true
+    }
+
+    fn execute_chip8x(&mut self, opcode: u16) -> bool {
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+        let y = ((opcode & 0x00F0) >> 4) as usize;
+        let n = (opcode & 0x000F) as usize;
+        let pc = self.register.pc;
+
+        match opcode & 0xF000 {
+            _ if opcode == 0x02A0 => {
+                //02A0
+                // Step the background colour
+                self.frame_buffer.next_background();
+                self.draw_count += 1;
+            }
+            0x5000 if n == 1 => {
+                //5xy1
+                // Add Vy to Vx nibble by nibble, each wrapping at 8
+                let vx = self.register.v_registers[x];
+                let vy = self.register.v_registers[y];
+                self.register.v_registers[x] = ((vx & 0x77) + (vy & 0x77)) & 0x77;
+            }
+            0xB000 => {
+                // Vx is the horizontal position in 8 pixel columns, Vx+1 the vertical
+                // in 4 pixel rows, Vy the colour
+                let horizontal = self.register.v_registers[x] as usize;
+                let vertical = self.register.v_registers[(x + 1) & 0xF] as usize;
+                let color = self.register.v_registers[y];
+
+                let (columns, rows) = if n == 0 {
+                    //Bxy0
+                    // Colour a block of zones, the low nibbles are the top left zone
+                    // and the high nibbles how many more zones it extends by
+                    let left = horizontal & 0xF;
+                    let top = vertical & 0xF;
+                    (left..=left + (horizontal >> 4), top..=top + (vertical >> 4))
+                } else {
+                    //Bxyn
+                    // Colour the zones under an 8 pixel wide, n row sprite at (Vx, Vx+1)
+                    let column = horizontal / 8;
+                    (column..=column, vertical / 4..=(vertical + n - 1) / 4)
+                };
+                for row in rows {
+                    for column in columns.clone() {
+                        self.frame_buffer.set_zone_color(column, row, color);
+                    }
+                }
+                self.draw_count += 1;
+            }
+            0xE000 if opcode & 0xFF == 0xF2 => {
+                //ExF2
+                // Skip next instruction if key Vx is pressed on the second keypad
+                let key = self.register.v_registers[x] & 0xF;
+                if self.is_key_down(Keypad::Second, key as usize) {
+                    self.register.pc = pc.wrapping_add(2);
+                }
+            }
+            0xE000 if opcode & 0xFF == 0xF5 => {
+                //ExF5
+                // Skip next instruction if key Vx is not pressed on the second keypad
+                let key = self.register.v_registers[x] & 0xF;
+                if !self.is_key_down(Keypad::Second, key as usize) {
+                    self.register.pc = pc.wrapping_add(2);
+                }
+            }
+            0xF000 if opcode & 0xFF == 0xF8 => {
+                //FxF8
+                // Output Vx to port 3, the VP-595 sound board's tone
+                self.output_port = self.register.v_registers[x];
+            }
+            0xF000 if opcode & 0xFF == 0xFB => {
+                //FxFB
+                // Read input port 3 into Vx, nothing is connected so it reads 0
+                self.register.v_registers[x] = 0;
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /*
+     * The last value a CHIP-8E or CHIP-8X program wrote to output port 3
+     */
+    pub fn get_output_port(&self) -> u8 {
+        self.output_port
+    }
+
+    /*
+     * The HP48's RPL user flags, saved by Fx75 and restored by Fx85
+     */
+    pub fn rpl_flags(&self) -> &[u8; RPL_FLAGS] {
+        &self.rpl
+    }
+
+    pub fn set_rpl_flags(&mut self, flags: [u8; RPL_FLAGS]) {
+        self.rpl = flags;
+    }
+
+    /*
+     * A single emulated 60Hz frame
+     * executes up to the given number of instructions, then reaches the frame boundary.
+     * With the display wait quirk a draw ends the frame early.
+     *
+     * Under the VIP timing model `instructions` is ignored, the frame lasts as many
+     * instructions as fit in the interpreter's machine cycle budget.
+     *
+     * A machine error ends the frame on the failing instruction, without the interrupt.
+     */
+    pub fn run_frame(&mut self, instructions: u32) -> Result<(), MachineError> {
+        self.sound_edges.clear();
+        self.sample_commands.clear();
+
+        if self.vip.is_some() {
+            self.run_vip_frame();
+            return Ok(());
+        }
+
+        match self.timing {
+            TimingModel::Instructions => {
+                for i in 0..instructions {
+                    self.run()?;
+                    self.observe_sound((i + 1) as f32 / instructions as f32);
+                    if self.waiting_for_vblank {
+                        break;
+                    }
+                }
+            }
+            TimingModel::Vip => self.run_cycles()?,
+        }
+
+        self.vblank();
+        Ok(())
+    }
+
+    /*
+     * An instruction running past the end of the frame finishes, the timers are only
+     * decremented once it does and its overrun is taken from the next frame.
+     * Draws wait for the interrupt and then draw, so their whole cost lands in the next frame.
+     */
+    fn run_cycles(&mut self) -> Result<(), MachineError> {
+        let budget = timing::INTERPRETER_CYCLES;
+        let mut used = self.cycle_debt;
+        self.cycle_debt = 0;
+
+        while used < budget {
+            let pc = self.register.pc;
+            let opcode = (self.read_memory(pc as usize) as u16) << 8
+                | self.read_memory(pc as usize + 1) as u16;
+            let vx = self.register.v_registers[((opcode & 0x0F00) >> 8) as usize];
+
+            self.run()?;
+
+            let skipped = self.register.pc == pc.wrapping_add(4);
+            let cost = timing::cycles(opcode, vx, skipped);
+            if self.waiting_for_vblank {
+                self.cycle_debt = cost;
+                self.observe_sound(used as f32 / budget as f32);
+                return Ok(());
+            }
+
+            used += cost;
+            self.observe_sound(used.min(budget) as f32 / budget as f32);
+        }
+        self.cycle_debt = used - budget;
+        Ok(())
+    }
+
+    /*
+     * The VIP runs a whole CDP1861 frame, its picture is copied into the frame buffer
+     * at 64x128 and every change counts as a draw
+     */
+    fn run_vip_frame(&mut self) {
+        let keypad: [bool; 16] = std::array::from_fn(|key| self.is_key_down(Keypad::First, key));
+        let Some(vip) = self.vip.as_mut() else {
+            return;
+        };
+        vip.set_keypad(&keypad);
+        vip.run_frame();
+        self.sound_edges.extend_from_slice(vip.sound_edges());
+        if let Some(edge) = self.sound_edges.last() {
+            self.sound_on = edge.on;
+        }
+
+        let (width, height) = (vip::cdp1861::WIDTH, vip::cdp1861::HEIGHT);
+        if self.frame_buffer.width() != width || self.frame_buffer.height() != height {
+            self.frame_buffer.resize(width, height);
+        }
+
+        let mut changed = false;
+        for (i, pixel) in vip.pixels().iter().enumerate() {
+            let (x, y) = (i % width, i / width);
+            if self.frame_buffer.get(x, y) != *pixel {
+                self.frame_buffer.set(x, y, *pixel);
+                changed = true;
+            }
+        }
+        if changed {
+            self.draw_count += 1;
+            if let Some(picture) = self.picture.as_mut() {
+                picture.drawn(&self.frame_buffer, false);
+            }
+        }
+        self.frame_buffer.next_frame();
+        self.key_presses = [[false; 16]; 2];
+    }
+
+    /*
+     * True once a draw has been executed under the display wait quirk,
+     * nothing more should run until `vblank`
+     */
+    pub fn is_waiting_for_vblank(&self) -> bool {
+        self.waiting_for_vblank
+    }
+
+    /*
+     * The frame boundary, the 60Hz interrupt that ticks the timers and releases a waiting draw
+     */
+    pub fn vblank(&mut self) {
+        self.waiting_for_vblank = false;
+        self.update_timers();
+        self.observe_sound(1.0);
+        if let Some(picture) = self.picture.as_mut() {
+            picture.vblank(&self.frame_buffer);
+        }
+        self.frame_buffer.next_frame();
+        self.key_presses = [[false; 16]; 2];
+    }
+
+    /*
+     * Key edges from the host, a key pressed since the last frame counts as down for
+     * the whole of the next one, so a tap released before it ran is not missed
+     */
+    pub fn press_key(&mut self, keypad: Keypad, key: u8) {
+        self.keypad_mut(keypad)[key as usize] = true;
+        self.key_presses[keypad as usize][key as usize] = true;
+    }
+
+    pub fn release_key(&mut self, keypad: Keypad, key: u8) {
+        self.keypad_mut(keypad)[key as usize] = false;
+    }
+
+    fn keypad_mut(&mut self, keypad: Keypad) -> &mut [bool; 16] {
+        match keypad {
+            Keypad::First => &mut self.keypad,
+            Keypad::Second => &mut self.second_keypad,
+        }
+    }
+
+    fn is_key_down(&self, keypad: Keypad, key: usize) -> bool {
+        let held = match keypad {
+            Keypad::First => self.keypad[key],
+            Keypad::Second => self.second_keypad[key],
+        };
+        held || self.key_presses[keypad as usize][key]
+    }
+
+    /*
+     * Frames emulated so far, counted at every `vblank`
+     */
+    pub fn frame(&self) -> u64 {
+        self.frame_buffer.frame()
+    }
+
+    /*
+     * Sound timer transitions during the last frame run by `run_frame`
+     * these place the beeper on the emulated timeline rather than the host's
+     */
+    pub fn sound_edges(&self) -> &[SoundEdge] {
+        &self.sound_edges
+    }
+
+    fn observe_sound(&mut self, position: f32) {
+        let on = self.register.sound_timer > 0;
+        if on != self.sound_on {
+            self.sound_on = on;
+            self.sound_edges.push(SoundEdge { position, on });
+        }
     }
 
     pub fn update_timers(&mut self) {
@@ -469,13 +1297,295 @@ impl CPU {
         }
     }
 
+    /*
+     * Present on draw only shows complete pictures, never one caught between
+     * a sprite being erased and drawn again (see `Picture`)
+     */
+    pub fn present_on_draw(&self) -> bool {
+        self.picture.is_some()
+    }
+
+    pub fn set_present_on_draw(&mut self, enabled: bool) {
+        self.picture = enabled.then(|| Picture::new(&self.frame_buffer));
+    }
+
+    /*
+     * The picture to show after the frame just run, None when it has not changed
+     */
+    pub fn take_picture(&mut self) -> Option<&FrameBuffer> {
+        match self.picture.as_mut() {
+            Some(picture) => picture.take(),
+            None => self.frame_buffer.take_dirty().map(|_| &self.frame_buffer),
+        }
+    }
+
+    /*
+     * Running count of draw (Dxyn) and clear (00E0) instructions executed
+     */
+    pub fn draw_count(&self) -> u64 {
+        self.draw_count
+    }
+
     pub fn get_sound_timer(&self) -> u8 {
-        self.register.sound_timer
+        match &self.vip {
+            Some(vip) => vip.chip8_sound_timer(),
+            None => self.register.sound_timer,
+        }
+    }
+
+    pub fn get_delay_timer(&self) -> u8 {
+        match &self.vip {
+            Some(vip) => vip.chip8_delay_timer(),
+            None => self.register.delay_timer,
+        }
+    }
+
+    pub fn get_v_registers(&self) -> &[u8; 16] {
+        match &self.vip {
+            Some(vip) => vip.chip8_registers(),
+            None => &self.register.v_registers,
+        }
+    }
+
+    pub fn get_index_register(&self) -> u32 {
+        match &self.vip {
+            Some(vip) => vip.chip8_index() as u32,
+            None => self.register.index_register,
+        }
+    }
+
+    /*
+     * The subroutines the program is in, innermost call first
+     */
+    pub fn backtrace(&self) -> Vec<StackFrame> {
+        self.stack
+            .iter()
+            .rev()
+            .map(|&caller| StackFrame {
+                caller,
+                return_address: caller.wrapping_add(2),
+            })
+            .collect()
+    }
+
+    pub fn get_pc(&self) -> u16 {
+        match &self.vip {
+            Some(vip) => vip.chip8_pc(),
+            None => self.register.pc,
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_wait() {
+        //three draws of a one byte sprite
+        let program = [0xD0, 0x01, 0xD0, 0x01, 0xD0, 0x01];
+
+        let mut cpu = CPU::new();
+        cpu.load_rom(&program).unwrap();
+        cpu.run_frame(10).unwrap();
+        assert_eq!(cpu.draw_count(), 3);
+
+        let mut cpu = CPU::new();
+        cpu.quirks.display_wait = true;
+        cpu.load_rom(&program).unwrap();
+        cpu.run_frame(10).unwrap();
+        assert_eq!((cpu.draw_count(), cpu.get_pc()), (1, 0x202));
+        assert!(!cpu.is_waiting_for_vblank());
+        assert_eq!(cpu.frame(), 1);
+    }
+
+    #[test]
+    fn test_vip_frames() {
+        //a clear, then V0 counts up in a loop
+        let program = [0x00, 0xE0, 0x70, 0x01, 0x12, 0x02];
+        let mut cpu = CPU::new();
+        cpu.timing = TimingModel::Vip;
+        cpu.load_rom(&program).unwrap();
+
+        //3118 cycles of clear, 1288 of them are taken from the second frame
+        cpu.run_frame(0).unwrap();
+        assert_eq!((cpu.get_pc(), cpu.get_v_registers()[0]), (0x202, 0));
+        //then 102 cycles a loop
+        cpu.run_frame(0).unwrap();
+        assert_eq!(cpu.get_v_registers()[0], 6);
+        cpu.run_frame(0).unwrap();
+        assert_eq!(cpu.get_v_registers()[0], 24);
+
+        //a draw waits for the interrupt, one per frame
+        let mut cpu = CPU::new();
+        cpu.timing = TimingModel::Vip;
+        cpu.load_rom(&[0xD0, 0x15, 0x12, 0x00]).unwrap();
+        for _ in 0..5 {
+            cpu.run_frame(0).unwrap();
+        }
+        assert_eq!(cpu.draw_count(), 5);
+    }
+
+    #[test]
+    fn test_machine_config() {
+        let mut cpu = CPU::new();
+        cpu.set_machine(MachineConfig {
+            font_address: 0x300,
+            memory_size: 1024,
+            ..MachineConfig::default()
+        })
+        .unwrap();
+        assert!(cpu.load_rom(&[0; 0x180]).is_err());
+
+        //jumping past the end of a small memory wraps around to the start
+        cpu.load_rom(&[0x1F, 0x00]).unwrap();
+        cpu.run_frame(2).unwrap();
+        assert_eq!(cpu.get_pc(), 0xF02);
+    }
+
+    #[test]
+    fn test_present_on_draw() {
+        //the 0 glyph is drawn, erased at the end of the first frame and drawn again in the next
+        let program = [
+            0x60, 0x00, 0xF0, 0x29, 0xD0, 0x15, 0xD0, 0x15, 0xD0, 0x15, 0x12, 0x0A,
+        ];
+        let lit = |picture: Option<&FrameBuffer>| picture.map(|p| p.lit().filter(|l| *l).count());
+
+        let mut cpu = CPU::new();
+        cpu.load_rom(&program).unwrap();
+        cpu.run_frame(4).unwrap();
+        assert_eq!(lit(cpu.take_picture()), Some(0));
+
+        let mut cpu = CPU::new();
+        cpu.set_present_on_draw(true);
+        cpu.load_rom(&program).unwrap();
+        assert_eq!(lit(cpu.take_picture()), Some(0));
+        cpu.run_frame(4).unwrap();
+        assert_eq!(lit(cpu.take_picture()), Some(14));
+        cpu.run_frame(4).unwrap();
+        assert_eq!(lit(cpu.take_picture()), Some(14));
+        //nothing drawn, nothing to present
+        cpu.run_frame(4).unwrap();
+        assert_eq!(lit(cpu.take_picture()), None);
+    }
+
+    #[test]
+    fn test_platforms() {
+        //hires starts past the 1260 patch and draws on 64x64
+        let mut cpu = CPU::new();
+        cpu.set_platform(Platform::Hires);
+        let mut program = vec![0x12, 0x60];
+        program.resize(0xC0, 0);
+        program.extend([0x60, 0x3C, 0xD0, 0x01]);
+        cpu.load_rom(&program).unwrap();
+        cpu.run_frame(2).unwrap();
+        assert!(cpu.frame_buffer.get(60, 60));
+
+        //CHIP-8E branches back over the skip
+        let mut cpu = CPU::new();
+        cpu.set_platform(Platform::Chip8E);
+        cpu.load_rom(&[0x70, 0x01, 0x51, 0x01, 0xBB, 0x04]).unwrap();
+        cpu.run_frame(6).unwrap();
+        assert_eq!(cpu.get_v_registers()[0], 2);
+
+        //and stores registers past the end of memory at the start
+        let mut cpu = CPU::new();
+        cpu.set_platform(Platform::Chip8E);
+        cpu.load_rom(&[0x60, 0xAB, 0xAF, 0xFE, 0x50, 0xF2]).unwrap();
+        cpu.run_frame(3).unwrap();
+        assert_eq!(cpu.memory[0xFFE], 0xAB);
+        assert_eq!(cpu.get_index_register(), 0x100E);
+
+        //CHIP-8X loads at 0x300 and colours the zones under a sprite
+        let mut cpu = CPU::new();
+        cpu.set_platform(Platform::Chip8X);
+        cpu.load_rom(&[0x61, 0x04, 0x62, 0x06, 0xB0, 0x25, 0x02, 0xA0])
+            .unwrap();
+        cpu.run_frame(4).unwrap();
+        let colors = cpu.frame_buffer.colors().unwrap();
+        assert_eq!((colors.zone(0, 1), colors.zone(0, 2)), (6, 6));
+        assert_eq!(
+            (colors.zone(0, 0), colors.zone(0, 3), colors.zone(1, 1)),
+            (1, 1, 1)
+        );
+        assert_eq!(colors.background(), 1);
+
+        //SCHIP hires clips a 16x16 sprite at the right edge
+        let mut cpu = CPU::new();
+        cpu.set_platform(Platform::SuperChip);
+        cpu.load_rom(&[
+            0x00, 0xFF, 0x60, 0x7C, 0xA2, 0x0A, 0xD0, 0x10, 0x12, 0x08, 0xFF, 0xFF,
+        ])
+        .unwrap();
+        cpu.run_frame(5).unwrap();
+        assert_eq!(cpu.frame_buffer.width(), 128);
+        assert!(cpu.frame_buffer.get(127, 0) && !cpu.frame_buffer.get(0, 0));
+
+        //MegaChip's long I load reaches past 64K
+        let mut cpu = CPU::new();
+        cpu.set_platform(Platform::MegaChip);
+        cpu.load_rom(&[0x00, 0x11, 0x01, 0x12, 0x34, 0x56, 0x00, 0xE0])
+            .unwrap();
+        cpu.run_frame(3).unwrap();
+        assert_eq!(cpu.get_index_register(), 0x12_3456);
+        assert_eq!(cpu.frame_buffer.width(), 256);
+        assert!(cpu.frame_buffer.direct_colors().is_some());
+    }
+
+    #[test]
+    fn test_stack_depth() {
+        //each call calls the next instruction, 12 levels deep on the VIP
+        let program: Vec<u8> = (1..=13u16)
+            .flat_map(|i| (0x2200 + i * 2).to_be_bytes())
+            .collect();
+        let mut cpu = CPU::new();
+        cpu.load_rom(&program).unwrap();
+        assert_eq!(
+            cpu.run_frame(20),
+            Err(MachineError::StackOverflow {
+                pc: 0x218,
+                depth: 12
+            })
+        );
+        let backtrace = cpu.backtrace();
+        assert_eq!(backtrace.len(), 12);
+        assert_eq!(
+            (backtrace[0].caller, backtrace[0].return_address),
+            (0x216, 0x218)
+        );
+        assert_eq!(backtrace[11].caller, 0x200);
+
+        let mut cpu = CPU::new();
+        cpu.load_rom(&[0x00, 0xEE]).unwrap();
+        assert_eq!(
+            cpu.run_frame(1),
+            Err(MachineError::StackUnderflow { pc: 0x200 })
+        );
+        assert_eq!(cpu.get_pc(), 0x200);
+    }
+
+    #[test]
+    fn test_key_release() {
+        let mut cpu = CPU::new();
+        cpu.quirks.key_release = true;
+        cpu.load_rom(&[0xF0, 0x0A]).unwrap();
+        cpu.press_key(Keypad::First, 5);
+        cpu.run_frame(10).unwrap();
+        assert_eq!(cpu.get_pc(), 0x200);
+
+        cpu.release_key(Keypad::First, 5);
+        cpu.run_frame(1).unwrap();
+        assert_eq!((cpu.get_pc(), cpu.get_v_registers()[0]), (0x202, 5));
+
+        //a tap between two frames is still seen
+        let mut cpu = CPU::new();
+        cpu.quirks.key_release = true;
+        cpu.load_rom(&[0xF0, 0x0A]).unwrap();
+        cpu.press_key(Keypad::First, 7);
+        cpu.release_key(Keypad::First, 7);
+        cpu.run_frame(2).unwrap();
+        assert_eq!((cpu.get_pc(), cpu.get_v_registers()[0]), (0x202, 7));
+    }
 
     #[test]
     fn test_bcd() {
@@ -0,0 +1,100 @@
+use crate::chip8::machine::MachineConfig;
+use std::fmt;
+use std::str::FromStr;
+
+/*
+ * The CHIP-8 variant a program was written for
+ *
+ * Each one is the original interpreter with changes, so they differ in where programs
+ * are loaded and start, in the display and in a handful of instructions.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Platform {
+    /// The original COSMAC VIP interpreter, 64x32
+    #[default]
+    Chip8,
+    /// Two-page hires CHIP-8, 64x64. Programs begin with the 1260 patch and start at 0x2C0
+    Hires,
+    /// CHIP-8E, extra skips, branches and register block moves
+    Chip8E,
+    /// CHIP-8X for the VP-590 colour board and the second VP-580 keypad, loaded at 0x300
+    Chip8X,
+    /// SUPER-CHIP 1.1 for the HP48, 128x64 hires, big sprites and scrolling
+    SuperChip,
+    /// MegaChip8, SCHIP with a 256x192 colour mode and 24-bit addressing
+    MegaChip,
+}
+
+impl Platform {
+    /*
+     * The memory layout of the original interpreter
+     * hires skips over the interpreter patch loaded with the program
+     */
+    pub fn machine(self) -> MachineConfig {
+        let defaults = MachineConfig::default();
+        match self {
+            Self::Hires => MachineConfig {
+                start_address: 0x2C0,
+                ..defaults
+            },
+            Self::Chip8X => MachineConfig {
+                load_address: 0x300,
+                start_address: 0x300,
+                ..defaults
+            },
+            Self::SuperChip => MachineConfig {
+                stack_depth: 16,
+                ..defaults
+            },
+            Self::MegaChip => MachineConfig {
+                memory_size: 0x100_0000,
+                stack_depth: 16,
+                ..defaults
+            },
+            _ => defaults,
+        }
+    }
+
+    /*
+     * Whether the SCHIP instructions are there
+     */
+    pub fn is_schip(self) -> bool {
+        matches!(self, Self::SuperChip | Self::MegaChip)
+    }
+
+    pub fn resolution(self) -> (usize, usize) {
+        match self {
+            Self::Hires => (64, 64),
+            _ => (64, 32),
+        }
+    }
+}
+
+impl FromStr for Platform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "chip8" | "chip-8" => Ok(Self::Chip8),
+            "hires" | "hires-chip8" | "chip8-hires" => Ok(Self::Hires),
+            "chip8e" | "chip-8e" => Ok(Self::Chip8E),
+            "chip8x" | "chip-8x" => Ok(Self::Chip8X),
+            "schip" | "superchip" | "super-chip" => Ok(Self::SuperChip),
+            "megachip" | "megachip8" | "mega-chip" => Ok(Self::MegaChip),
+            _ => Err(format!("Unknown platform: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Chip8 => write!(f, "chip-8"),
+            Self::Hires => write!(f, "hires"),
+            Self::Chip8E => write!(f, "chip-8e"),
+            Self::Chip8X => write!(f, "chip-8x"),
+            Self::SuperChip => write!(f, "schip"),
+            Self::MegaChip => write!(f, "megachip"),
+        }
+    }
+}
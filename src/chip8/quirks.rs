@@ -0,0 +1,15 @@
+/*
+ * Behaviours that differ between CHIP-8 interpreters
+ *
+ * The defaults match this emulator's original behaviour, each flag switches to
+ * what some other interpreter did.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Quirks {
+    /// Dxyn waits for the next vertical blank, as on the COSMAC VIP,
+    /// so a draw is the last instruction of its frame
+    pub display_wait: bool,
+    /// Fx0A waits for the key to be pressed and released again, as on the COSMAC VIP,
+    /// instead of taking whichever key is held
+    pub key_release: bool,
+}
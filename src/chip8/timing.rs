@@ -0,0 +1,141 @@
+use std::fmt;
+use std::str::FromStr;
+
+/*
+ * How much CHIP-8 code runs in a 60Hz frame
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimingModel {
+    /// A fixed number of instructions per frame, whatever they are
+    #[default]
+    Instructions,
+    /// Machine cycle costs of the COSMAC VIP interpreter against the VIP's frame budget
+    Vip,
+}
+
+impl FromStr for TimingModel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "instructions" | "fixed" => Ok(Self::Instructions),
+            "vip" | "cycles" => Ok(Self::Vip),
+            _ => Err(format!("Unknown timing model: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for TimingModel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Instructions => write!(f, "instructions"),
+            Self::Vip => write!(f, "vip"),
+        }
+    }
+}
+
+/*
+ * The VIP's CDP1802 runs at 1.7609MHz with 8 clocks per machine cycle,
+ * 3668 machine cycles for every frame of the CDP1861
+ */
+pub const FRAME_CYCLES: u32 = 3668;
+/*
+ * While the 128 visible lines are drawn the interrupt routine keeps the CPU busy
+ * repeating each CHIP-8 row 4 times, 14 machine cycles per line.
+ * It also decrements the timers on the way in.
+ */
+pub const DISPLAY_CYCLES: u32 = 128 * 14;
+pub const INTERRUPT_CYCLES: u32 = 46;
+/*
+ * What is left for the interpreter every frame
+ */
+pub const INTERPRETER_CYCLES: u32 = FRAME_CYCLES - DISPLAY_CYCLES - INTERRUPT_CYCLES;
+
+/*
+ * The interpreter's fetch and decode loop, paid by every instruction
+ */
+const FETCH_CYCLES: u32 = 40;
+/*
+ * Taking a skip costs a little more than falling through
+ */
+const SKIP_CYCLES: u32 = 4;
+
+/*
+ * Machine cycles for one instruction including fetch and decode
+ *
+ * `vx` is Vx before the instruction ran and `skipped` whether a skip instruction skipped.
+ * The costs are counted from the CHIP-8 interpreter listing in the RCA COSMAC VIP
+ * Instruction Manual (VIP-311), as annotated in Laurence Scotford's disassembly
+ * "Chip-8 on the COSMAC VIP": every 1802 instruction takes 2 machine cycles and long
+ * branches 3. Loops are counted per iteration, so Fx33 depends on Vx and Dxyn on the
+ * sprite's height and alignment; rows clipped at the bottom edge are still counted.
+ *
+ * 00E0 is the machine code routine at 0x00E0, which stores a zero to each of the 256
+ * bytes of display memory at 12 machine cycles a byte after 6 to set up. That is more
+ * than a frame's whole interpreter budget, on the VIP a clear finishes in the next
+ * frame, which is what `CPU::run_cycles` does with the overrun.
+ */
+pub fn cycles(opcode: u16, vx: u8, skipped: bool) -> u32 {
+    let x = (opcode & 0x0F00) >> 8;
+    let n = (opcode & 0x000F) as u32;
+    let skip = if skipped { SKIP_CYCLES } else { 0 };
+
+    let execute = match opcode & 0xF000 {
+        0x0000 => match opcode {
+            //the clear loop writes all 256 bytes of display memory
+            0x00E0 => 3078,
+            0x00EE => 10,
+            _ => 0,
+        },
+        0x1000 => 12,
+        0x2000 => 26,
+        0x3000 | 0x4000 => 10 + skip,
+        0x5000 | 0x9000 => 14 + skip,
+        0x6000 => 6,
+        0x7000 => 10,
+        0x8000 => 44,
+        0xA000 => 12,
+        0xB000 => 22,
+        0xC000 => 36,
+        0xD000 => draw_cycles(vx, n),
+        0xE000 => 14 + skip,
+        0xF000 => match opcode & 0x00FF {
+            0x07 | 0x15 | 0x18 => 10,
+            //waiting is handled by re-running the instruction every fetch
+            0x0A => 10,
+            0x1E | 0x29 => 16,
+            //the VIP finds each digit by repeated subtraction
+            0x33 => {
+                let digits = (vx / 100 + (vx / 10) % 10 + vx % 10) as u32;
+                80 + 16 * digits
+            }
+            0x55 | 0x65 => 14 + 14 * (x as u32 + 1),
+            _ => 0,
+        },
+        _ => 0,
+    };
+
+    FETCH_CYCLES + execute
+}
+
+/*
+ * Sprites not aligned to a byte are shifted bit by bit into two bytes of display memory
+ */
+fn draw_cycles(vx: u8, rows: u32) -> u32 {
+    let shift = (vx % 8) as u32;
+    let row = if shift == 0 { 34 } else { 46 + 4 * shift };
+    26 + rows * row
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cycles() {
+        assert_eq!(cycles(0x6012, 0, false), 46);
+        assert!(cycles(0x3012, 0, true) > cycles(0x3012, 0, false));
+        assert!(cycles(0xD015, 3, false) > cycles(0xD015, 8, false));
+        assert!(cycles(0x00E0, 0, false) > INTERPRETER_CYCLES);
+    }
+}
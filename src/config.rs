@@ -0,0 +1,185 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/*
+ * User configuration, stored as a small INI file
+ *
+ *     [default]
+ *     palette = amber
+ *     persistence = fade:0.5
+ *     present_on_draw = true
+ *
+ *     [rom.0df2789f661358d8f7370e6cf93490c5bcd44b01]
+ *     name = Pong
+ *     palette = #000000 #FFFFFF
+ *
+ * `[default]` applies to every ROM. `[rom.<sha1>]` sections form the ROM database,
+ * their keys override the defaults for the ROM with that hash (see `rom::hash`).
+ */
+pub const DEFAULT_SECTION: &str = "default";
+
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    sections: Vec<(String, Vec<(String, String)>)>,
+}
+
+impl Config {
+    /*
+     * $CHIP8_CONFIG, otherwise config.ini in the data directory
+     */
+    pub fn path() -> PathBuf {
+        match env::var_os("CHIP8_CONFIG") {
+            Some(path) => PathBuf::from(path),
+            None => data_dir().join("config.ini"),
+        }
+    }
+
+    /*
+     * A missing config file is the same as an empty one
+     */
+    pub fn load() -> io::Result<Self> {
+        match fs::read_to_string(Self::path()) {
+            Ok(text) => Ok(Self::parse(&text)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let path = Self::path();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, self.to_string())
+    }
+
+    pub fn parse(text: &str) -> Self {
+        let mut config = Self::default();
+        let mut section = DEFAULT_SECTION.to_string();
+
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                section = name.trim().to_string();
+                continue;
+            }
+
+            if let Some((key, value)) = line.split_once('=') {
+                config.set(&section, key.trim(), value.trim());
+            }
+        }
+        config
+    }
+
+    pub fn rom_section(hash: &str) -> String {
+        format!("rom.{}", hash)
+    }
+
+    pub fn section(&self, name: &str) -> Option<&[(String, String)]> {
+        self.sections
+            .iter()
+            .find(|(section, _)| section == name)
+            .map(|(_, entries)| entries.as_slice())
+    }
+
+    pub fn get_in(&self, section: &str, key: &str) -> Option<&str> {
+        self.section(section)?
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /*
+     * Look a key up for a ROM, falling back to the defaults
+     */
+    pub fn get(&self, rom_hash: Option<&str>, key: &str) -> Option<&str> {
+        rom_hash
+            .and_then(|hash| self.get_in(&Self::rom_section(hash), key))
+            .or_else(|| self.get_in(DEFAULT_SECTION, key))
+    }
+
+    pub fn set(&mut self, section: &str, key: &str, value: &str) {
+        let index = match self.sections.iter().position(|(s, _)| s == section) {
+            Some(index) => index,
+            None => {
+                self.sections.push((section.to_string(), Vec::new()));
+                self.sections.len() - 1
+            }
+        };
+
+        let entries = &mut self.sections[index].1;
+        match entries.iter_mut().find(|(k, _)| k == key) {
+            Some(entry) => entry.1 = value.to_string(),
+            None => entries.push((key.to_string(), value.to_string())),
+        }
+    }
+
+    /*
+     * Removes a key, and its section once that is empty
+     */
+    pub fn remove(&mut self, section: &str, key: &str) {
+        if let Some(index) = self.sections.iter().position(|(s, _)| s == section) {
+            self.sections[index].1.retain(|(k, _)| k != key);
+            if self.sections[index].1.is_empty() {
+                self.sections.remove(index);
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Config {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, (section, entries)) in self.sections.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            writeln!(f, "[{}]", section)?;
+            for (key, value) in entries {
+                writeln!(f, "{} = {}", key, value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/*
+ * Where the emulator keeps its files
+ * $CHIP8_DATA_DIR, $XDG_DATA_HOME/chip-8, ~/.local/share/chip-8 or %APPDATA%\chip-8
+ */
+pub fn data_dir() -> PathBuf {
+    if let Some(dir) = env::var_os("CHIP8_DATA_DIR") {
+        return PathBuf::from(dir);
+    }
+    if let Some(dir) = env::var_os("XDG_DATA_HOME") {
+        return PathBuf::from(dir).join("chip-8");
+    }
+    if let Some(dir) = env::var_os("APPDATA") {
+        return PathBuf::from(dir).join("chip-8");
+    }
+    match env::var_os("HOME") {
+        Some(home) => PathBuf::from(home).join(".local/share/chip-8"),
+        None => PathBuf::from(".chip-8"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rom_override() {
+        let config = Config::parse("[default]\npalette = green\n\n[rom.abc]\npalette = amber\n");
+        assert_eq!(config.get(Some("abc"), "palette"), Some("amber"));
+        assert_eq!(config.get(Some("def"), "palette"), Some("green"));
+        assert_eq!(
+            Config::parse(&config.to_string()).get(None, "palette"),
+            Some("green")
+        );
+    }
+}
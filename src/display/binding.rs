@@ -0,0 +1,263 @@
+use crate::config::Config;
+use crate::display::input::{HOTKEYS, Input, Profile};
+use crate::display::osd::KEYPAD_LAYOUT;
+use sdl2::keyboard::Keycode;
+use std::fmt;
+
+/*
+ * Rebinding the keypad from inside the window
+ *
+ * The screen walks through the 16 CHIP-8 keys in keypad order. Each host key pressed is
+ * bound to the highlighted key and moves on to the next. A host key that is already bound
+ * to another key, in this or any other active profile, or is a hotkey is refused.
+ *
+ * Escape cancels, Backspace goes back a key, Delete unbinds it, F3 edits the next active
+ * profile, F1 saves for every ROM and F2 for this ROM only. Each is saved as just the keys
+ * that differ from the layers below it, so a ROM's own keys never become global.
+ */
+#[derive(Debug, Clone)]
+pub struct BindingScreen {
+    profiles: Vec<Profile>,
+    /// The profiles as they were loaded, to tell which keys were changed
+    original: Vec<Profile>,
+    rom_hash: Option<String>,
+    current: usize,
+    selected: usize,
+    conflict: Option<Conflict>,
+}
+
+/*
+ * Why a host key was refused
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Conflict {
+    /// Already bound to a CHIP-8 key
+    Bound {
+        key: Keycode,
+        button: u8,
+        profile: String,
+    },
+    Hotkey(Keycode),
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bound {
+                key,
+                button,
+                profile,
+            } => write!(f, "{} is already {:X} in {}", key.name(), button, profile),
+            Self::Hotkey(key) => write!(f, "{} is an emulator hotkey", key.name()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BindingResult {
+    Cancel,
+    /// Save, for this ROM only when true
+    Save(bool),
+}
+
+impl BindingScreen {
+    pub fn new(input: &Input, rom_hash: Option<&str>) -> Self {
+        Self {
+            profiles: input.profiles().to_vec(),
+            original: input.profiles().to_vec(),
+            rom_hash: rom_hash.map(str::to_string),
+            current: 0,
+            selected: 0,
+            conflict: None,
+        }
+    }
+
+    pub fn profile(&self) -> &Profile {
+        &self.profiles[self.current]
+    }
+
+    /*
+     * The CHIP-8 key waiting for a host key
+     */
+    pub fn selected(&self) -> u8 {
+        KEYPAD_LAYOUT[self.selected / 4][self.selected % 4]
+    }
+
+    pub fn conflict(&self) -> Option<&Conflict> {
+        self.conflict.as_ref()
+    }
+
+    pub fn key(&mut self, key: Keycode) -> Option<BindingResult> {
+        self.conflict = None;
+        match key {
+            Keycode::ESCAPE => return Some(BindingResult::Cancel),
+            Keycode::F1 => return Some(BindingResult::Save(false)),
+            Keycode::F2 => return Some(BindingResult::Save(true)),
+            Keycode::F3 => {
+                self.current = (self.current + 1) % self.profiles.len();
+                self.selected = 0;
+            }
+            Keycode::BACKSPACE => self.selected = (self.selected + 15) % 16,
+            Keycode::DELETE => {
+                let button = self.selected();
+                self.profiles[self.current].keys[button as usize] = None;
+                self.selected = (self.selected + 1) % 16;
+            }
+            key => self.bind(key),
+        }
+        None
+    }
+
+    fn bind(&mut self, key: Keycode) {
+        let button = self.selected();
+        let conflict = self.profiles.iter().enumerate().find_map(|(i, profile)| {
+            let bound = profile.button(key)?;
+            (i != self.current || bound != button).then(|| Conflict::Bound {
+                key,
+                button: bound,
+                profile: profile.name.clone(),
+            })
+        });
+        let conflict = conflict.or(HOTKEYS.contains(&key).then_some(Conflict::Hotkey(key)));
+
+        if conflict.is_some() {
+            self.conflict = conflict;
+            return;
+        }
+        self.profiles[self.current].keys[button as usize] = Some(key);
+        self.selected = (self.selected + 1) % 16;
+    }
+
+    /*
+     * Writes every active profile into the bindings, the config is only read for the
+     * layers below them
+     */
+    pub fn save(
+        &self,
+        config: &Config,
+        bindings: &mut Config,
+        for_rom: bool,
+    ) -> Result<(), String> {
+        for (profile, original) in self.profiles.iter().zip(&self.original) {
+            match self.rom_hash.as_deref().filter(|_| for_rom) {
+                Some(hash) => {
+                    let global = Profile::section(None, &profile.name);
+                    let mut below = Profile::base(&profile.name);
+                    below.apply(config, &global)?;
+                    below.apply(bindings, &global)?;
+                    below.apply(config, &Profile::section(Some(hash), &profile.name))?;
+                    profile.save(&below, bindings, Some(hash));
+                }
+                None => self.save_global(profile, original, config, bindings)?,
+            }
+        }
+        Ok(())
+    }
+
+    /*
+     * Only the keys changed on the screen are saved globally, not the ones this ROM
+     * overrides, and they are dropped from the ROM's bindings so they take effect here too
+     */
+    fn save_global(
+        &self,
+        profile: &Profile,
+        original: &Profile,
+        config: &Config,
+        bindings: &mut Config,
+    ) -> Result<(), String> {
+        let section = Profile::section(None, &profile.name);
+        let mut below = Profile::base(&profile.name);
+        below.apply(config, &section)?;
+        let mut saved = below.clone();
+        saved.apply(bindings, &section)?;
+
+        let rom = self
+            .rom_hash
+            .as_deref()
+            .map(|hash| Profile::section(Some(hash), &profile.name));
+        if profile.keypad != original.keypad {
+            saved.keypad = profile.keypad;
+            if let Some(rom) = &rom {
+                bindings.remove(rom, "keypad");
+            }
+        }
+        for button in 0..16 {
+            if profile.keys[button] != original.keys[button] {
+                saved.keys[button] = profile.keys[button];
+                if let Some(rom) = &rom {
+                    bindings.remove(rom, &format!("{:X}", button));
+                }
+            }
+        }
+        saved.save(&below, bindings, None);
+        Ok(())
+    }
+
+    /*
+     * The active profiles as they load once saved
+     */
+    pub fn reload(&self, configs: &[&Config]) -> Result<Input, String> {
+        let names: Vec<_> = self.original.iter().map(|p| p.name.as_str()).collect();
+        Input::load(configs, self.rom_hash.as_deref(), &names.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conflict() {
+        let mut screen = BindingScreen::new(&Input::default(), None);
+        assert_eq!(screen.selected(), 0x1);
+
+        //R is already D
+        screen.key(Keycode::R);
+        assert_eq!(screen.selected(), 0x1);
+        assert!(matches!(
+            screen.conflict(),
+            Some(Conflict::Bound { button: 0xD, .. })
+        ));
+        screen.key(Keycode::M);
+        assert_eq!(screen.conflict(), Some(&Conflict::Hotkey(Keycode::M)));
+
+        //binding a key to itself is not a conflict
+        screen.key(Keycode::NUM_1);
+        assert_eq!(screen.selected(), 0x2);
+        screen.key(Keycode::P);
+        assert_eq!(screen.profile().keys[0x2], Some(Keycode::P));
+        assert_eq!(screen.key(Keycode::F2), Some(BindingResult::Save(true)));
+    }
+
+    #[test]
+    fn test_save_layers() {
+        let config = Config::parse("[input.keyboard]\nkeypad = 2\n");
+        let mut bindings = Config::parse("[rom.abc.input.keyboard]\n1 =\n");
+        let input = Input::load(&[&config, &bindings], Some("abc"), "keyboard").unwrap();
+        assert_eq!(input.profiles()[0].keys[0x1], None);
+
+        //unbinding 1 changes nothing, 2 only for this ROM
+        let mut screen = BindingScreen::new(&input, Some("abc"));
+        screen.key(Keycode::DELETE);
+        screen.key(Keycode::DELETE);
+        screen.save(&config, &mut bindings, true).unwrap();
+        let rom = "rom.abc.input.keyboard";
+        assert_eq!(bindings.get_in(rom, "1"), Some(""));
+        assert_eq!(bindings.get_in(rom, "2"), Some(""));
+        assert_eq!(bindings.get_in(rom, "keypad"), None);
+        assert!(bindings.section("input.keyboard").is_none());
+
+        //saving globally takes 3, not the ROM's 1 and 2
+        let input = screen.reload(&[&config, &bindings]).unwrap();
+        let mut screen = BindingScreen::new(&input, Some("abc"));
+        for _ in 0..3 {
+            screen.key(Keycode::DELETE);
+        }
+        screen.save(&config, &mut bindings, false).unwrap();
+        assert_eq!(bindings.get_in("input.keyboard", "1"), None);
+        assert_eq!(bindings.get_in("input.keyboard", "2"), None);
+        assert_eq!(bindings.get_in("input.keyboard", "3"), Some(""));
+        assert_eq!(bindings.get_in("input.keyboard", "keypad"), None);
+        assert_eq!(bindings.get_in(rom, "2"), Some(""));
+    }
+}
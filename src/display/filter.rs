@@ -0,0 +1,514 @@
+use sdl2::pixels::Color;
+use std::fmt;
+use std::str::FromStr;
+
+/*
+ * Post-processing done in software
+ *
+ * Filters take the shaded phosphor image at the CHIP-8's resolution and produce an
+ * 0xAARRGGBB image at the window's resolution, ready to upload to a streaming texture.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Filter {
+    #[default]
+    None,
+    Scanlines,
+    Grid,
+    Bloom,
+    Crt,
+    Scale2x,
+    Scale3x,
+    Smooth,
+}
+
+const FILTERS: [Filter; 8] = [
+    Filter::None,
+    Filter::Scanlines,
+    Filter::Grid,
+    Filter::Bloom,
+    Filter::Crt,
+    Filter::Scale2x,
+    Filter::Scale3x,
+    Filter::Smooth,
+];
+
+const SCANLINE_DEPTH: f32 = 0.45;
+const GRID_SHADOW: f32 = 0.35;
+const BLOOM_STRENGTH: f32 = 0.6;
+const BARREL_AMOUNT: f32 = 0.08;
+/*
+ * Squared colour distance below which the Smooth upscaler treats two pixels as the same
+ */
+const SMOOTH_THRESHOLD: i32 = 48 * 48;
+
+impl Filter {
+    pub fn next(self) -> Self {
+        let index = FILTERS.iter().position(|f| *f == self).unwrap_or(0);
+        FILTERS[(index + 1) % FILTERS.len()]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Scanlines => "scanlines",
+            Self::Grid => "grid",
+            Self::Bloom => "bloom",
+            Self::Crt => "crt",
+            Self::Scale2x => "scale2x",
+            Self::Scale3x => "scale3x",
+            Self::Smooth => "smooth",
+        }
+    }
+}
+
+impl FromStr for Filter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        FILTERS
+            .iter()
+            .find(|f| f.name().eq_ignore_ascii_case(s.trim()))
+            .copied()
+            .ok_or_else(|| format!("Unknown filter: {}", s))
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/*
+ * A pixel buffer of 0xAARRGGBB values
+ */
+#[derive(Debug, Clone, Default)]
+pub struct Frame {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u32>,
+}
+
+impl Frame {
+    fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![0; width * height],
+        }
+    }
+
+    fn get(&self, x: isize, y: isize) -> u32 {
+        let x = x.clamp(0, self.width as isize - 1) as usize;
+        let y = y.clamp(0, self.height as isize - 1) as usize;
+        self.pixels[y * self.width + x]
+    }
+}
+
+/*
+ * Run `filter` over the shaded pixels and scale the result to `out_width` x `out_height`
+ */
+pub fn apply(
+    filter: Filter,
+    colors: &[Color],
+    width: usize,
+    height: usize,
+    out_width: usize,
+    out_height: usize,
+) -> Frame {
+    let source = colorize(colors, width, height);
+
+    let upscaled = match filter {
+        Filter::Scale2x => scale2x(&source),
+        Filter::Scale3x => scale3x(&source),
+        Filter::Smooth => smooth2x(&source),
+        _ => source.clone(),
+    };
+    let mut frame = resize(&upscaled, out_width, out_height);
+
+    let cell_width = out_width as f32 / width as f32;
+    let cell_height = out_height as f32 / height as f32;
+
+    match filter {
+        Filter::Scanlines => scanlines(&mut frame, cell_height),
+        Filter::Grid => grid(&mut frame, cell_width, cell_height),
+        Filter::Bloom => bloom(&mut frame, &source),
+        Filter::Crt => {
+            bloom(&mut frame, &source);
+            scanlines(&mut frame, cell_height);
+            frame = barrel(&frame, BARREL_AMOUNT);
+        }
+        _ => {}
+    }
+    frame
+}
+
+pub fn colorize(colors: &[Color], width: usize, height: usize) -> Frame {
+    let pixels = colors
+        .iter()
+        .map(|color| pack(color.r, color.g, color.b))
+        .collect();
+
+    Frame {
+        width,
+        height,
+        pixels,
+    }
+}
+
+fn pack(r: u8, g: u8, b: u8) -> u32 {
+    0xFF00_0000 | (r as u32) << 16 | (g as u32) << 8 | b as u32
+}
+
+fn unpack(pixel: u32) -> [f32; 3] {
+    [
+        ((pixel >> 16) & 0xFF) as f32,
+        ((pixel >> 8) & 0xFF) as f32,
+        (pixel & 0xFF) as f32,
+    ]
+}
+
+fn pack_f32([r, g, b]: [f32; 3]) -> u32 {
+    pack(
+        r.clamp(0.0, 255.0) as u8,
+        g.clamp(0.0, 255.0) as u8,
+        b.clamp(0.0, 255.0) as u8,
+    )
+}
+
+fn scale_pixel(pixel: u32, factor: f32) -> u32 {
+    let [r, g, b] = unpack(pixel);
+    pack_f32([r * factor, g * factor, b * factor])
+}
+
+fn mix(a: u32, b: u32, weight: f32) -> u32 {
+    let [ar, ag, ab] = unpack(a);
+    let [br, bg, bb] = unpack(b);
+    pack_f32([
+        ar + (br - ar) * weight,
+        ag + (bg - ag) * weight,
+        ab + (bb - ab) * weight,
+    ])
+}
+
+/*
+ * Nearest neighbour
+ */
+pub fn resize(source: &Frame, width: usize, height: usize) -> Frame {
+    let mut out = Frame::new(width, height);
+    for y in 0..height {
+        let sy = y * source.height / height;
+        let row = &source.pixels[sy * source.width..(sy + 1) * source.width];
+        for x in 0..width {
+            out.pixels[y * width + x] = row[x * source.width / width];
+        }
+    }
+    out
+}
+
+/*
+ * Scale2x / AdvMAME2x
+ *
+ *   A         E0 E1
+ * C P B  ->   E2 E3
+ *   D
+ */
+pub fn scale2x(source: &Frame) -> Frame {
+    let mut out = Frame::new(source.width * 2, source.height * 2);
+    for y in 0..source.height as isize {
+        for x in 0..source.width as isize {
+            let p = source.get(x, y);
+            let a = source.get(x, y - 1);
+            let b = source.get(x + 1, y);
+            let c = source.get(x - 1, y);
+            let d = source.get(x, y + 1);
+
+            let (mut e0, mut e1, mut e2, mut e3) = (p, p, p, p);
+            if c == a && c != d && a != b {
+                e0 = a;
+            }
+            if a == b && a != c && b != d {
+                e1 = b;
+            }
+            if d == c && d != b && c != a {
+                e2 = c;
+            }
+            if b == d && b != a && d != c {
+                e3 = d;
+            }
+
+            let (ox, oy) = (x as usize * 2, y as usize * 2);
+            out.pixels[oy * out.width + ox] = e0;
+            out.pixels[oy * out.width + ox + 1] = e1;
+            out.pixels[(oy + 1) * out.width + ox] = e2;
+            out.pixels[(oy + 1) * out.width + ox + 1] = e3;
+        }
+    }
+    out
+}
+
+/*
+ * Scale3x / AdvMAME3x
+ *
+ * A B C
+ * D E F
+ * G H I
+ */
+pub fn scale3x(source: &Frame) -> Frame {
+    let mut out = Frame::new(source.width * 3, source.height * 3);
+    for y in 0..source.height as isize {
+        for x in 0..source.width as isize {
+            let a = source.get(x - 1, y - 1);
+            let b = source.get(x, y - 1);
+            let c = source.get(x + 1, y - 1);
+            let d = source.get(x - 1, y);
+            let e = source.get(x, y);
+            let f = source.get(x + 1, y);
+            let g = source.get(x - 1, y + 1);
+            let h = source.get(x, y + 1);
+            let i = source.get(x + 1, y + 1);
+
+            let mut block = [e; 9];
+            if b != h && d != f {
+                if d == b {
+                    block[0] = d;
+                }
+                if (d == b && e != c) || (b == f && e != a) {
+                    block[1] = b;
+                }
+                if b == f {
+                    block[2] = f;
+                }
+                if (d == b && e != g) || (d == h && e != a) {
+                    block[3] = d;
+                }
+                if (b == f && e != i) || (h == f && e != c) {
+                    block[5] = f;
+                }
+                if d == h {
+                    block[6] = d;
+                }
+                if (d == h && e != i) || (h == f && e != g) {
+                    block[7] = h;
+                }
+                if h == f {
+                    block[8] = f;
+                }
+            }
+
+            let (ox, oy) = (x as usize * 3, y as usize * 3);
+            for (n, pixel) in block.iter().enumerate() {
+                out.pixels[(oy + n / 3) * out.width + ox + n % 3] = *pixel;
+            }
+        }
+    }
+    out
+}
+
+fn similar(a: u32, b: u32) -> bool {
+    let [ar, ag, ab] = unpack(a);
+    let [br, bg, bb] = unpack(b);
+    let (dr, dg, db) = ((ar - br) as i32, (ag - bg) as i32, (ab - bb) as i32);
+    dr * dr + dg * dg + db * db < SMOOTH_THRESHOLD
+}
+
+/*
+ * hqx-style 2x upscaler
+ *
+ * Like Scale2x it looks for diagonal edges between the neighbours, but compares
+ * colours with a tolerance and blends the corner towards the edge instead of
+ * copying it, which keeps faded phosphor pixels smooth.
+ */
+pub fn smooth2x(source: &Frame) -> Frame {
+    let mut out = Frame::new(source.width * 2, source.height * 2);
+    for y in 0..source.height as isize {
+        for x in 0..source.width as isize {
+            let p = source.get(x, y);
+            let up = source.get(x, y - 1);
+            let right = source.get(x + 1, y);
+            let left = source.get(x - 1, y);
+            let down = source.get(x, y + 1);
+
+            let corner = |a: u32, b: u32, opposite_a: u32, opposite_b: u32| {
+                if similar(a, b) && !similar(a, opposite_b) && !similar(b, opposite_a) {
+                    mix(p, mix(a, b, 0.5), 0.75)
+                } else if similar(a, b) && !similar(p, a) {
+                    mix(p, mix(a, b, 0.5), 0.25)
+                } else {
+                    p
+                }
+            };
+
+            let (ox, oy) = (x as usize * 2, y as usize * 2);
+            out.pixels[oy * out.width + ox] = corner(left, up, right, down);
+            out.pixels[oy * out.width + ox + 1] = corner(up, right, down, left);
+            out.pixels[(oy + 1) * out.width + ox] = corner(down, left, up, right);
+            out.pixels[(oy + 1) * out.width + ox + 1] = corner(right, down, left, up);
+        }
+    }
+    out
+}
+
+/*
+ * Darken the bottom of every emulated pixel row with a smooth falloff
+ */
+fn scanlines(frame: &mut Frame, cell_height: f32) {
+    if cell_height < 2.0 {
+        return;
+    }
+
+    for y in 0..frame.height {
+        let position = (y as f32 + 0.5) % cell_height / cell_height;
+        let brightness = (position * std::f32::consts::PI).sin();
+        let factor = 1.0 - SCANLINE_DEPTH * (1.0 - brightness * brightness);
+        let row = &mut frame.pixels[y * frame.width..(y + 1) * frame.width];
+        for pixel in row.iter_mut() {
+            *pixel = scale_pixel(*pixel, factor);
+        }
+    }
+}
+
+/*
+ * LCD shadow mask, a dark gap on the right and bottom edge of every emulated pixel
+ */
+fn grid(frame: &mut Frame, cell_width: f32, cell_height: f32) {
+    if cell_width < 3.0 || cell_height < 3.0 {
+        return;
+    }
+
+    for y in 0..frame.height {
+        let edge_y = (y as f32 + 1.0) % cell_height < 1.0;
+        for x in 0..frame.width {
+            let edge_x = (x as f32 + 1.0) % cell_width < 1.0;
+            if edge_x || edge_y {
+                let pixel = &mut frame.pixels[y * frame.width + x];
+                *pixel = scale_pixel(*pixel, GRID_SHADOW);
+            }
+        }
+    }
+}
+
+/*
+ * Glow around lit pixels
+ * the blur runs at the CHIP-8's resolution and is sampled bilinearly when added,
+ * which is far cheaper than blurring at window resolution
+ */
+fn bloom(frame: &mut Frame, source: &Frame) {
+    let blurred = box_blur(&box_blur(source));
+
+    let scale_x = source.width as f32 / frame.width as f32;
+    let scale_y = source.height as f32 / frame.height as f32;
+
+    for y in 0..frame.height {
+        let sy = (y as f32 + 0.5) * scale_y - 0.5;
+        for x in 0..frame.width {
+            let sx = (x as f32 + 0.5) * scale_x - 0.5;
+            let [gr, gg, gb] = bilinear(&blurred, sx, sy);
+            let index = y * frame.width + x;
+            let [r, g, b] = unpack(frame.pixels[index]);
+            frame.pixels[index] = pack_f32([
+                r + gr * BLOOM_STRENGTH,
+                g + gg * BLOOM_STRENGTH,
+                b + gb * BLOOM_STRENGTH,
+            ]);
+        }
+    }
+}
+
+fn box_blur(source: &Frame) -> Frame {
+    let mut out = Frame::new(source.width, source.height);
+    for y in 0..source.height as isize {
+        for x in 0..source.width as isize {
+            let mut sum = [0.0; 3];
+            for dy in -1..=1 {
+                for dx in -1..=1 {
+                    let [r, g, b] = unpack(source.get(x + dx, y + dy));
+                    sum[0] += r;
+                    sum[1] += g;
+                    sum[2] += b;
+                }
+            }
+            out.pixels[y as usize * source.width + x as usize] = pack_f32(sum.map(|c| c / 9.0));
+        }
+    }
+    out
+}
+
+fn bilinear(source: &Frame, x: f32, y: f32) -> [f32; 3] {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let (x0, y0) = (x0 as isize, y0 as isize);
+
+    let top = [source.get(x0, y0), source.get(x0 + 1, y0)].map(unpack);
+    let bottom = [source.get(x0, y0 + 1), source.get(x0 + 1, y0 + 1)].map(unpack);
+
+    let mut out = [0.0; 3];
+    for c in 0..3 {
+        let t = top[0][c] + (top[1][c] - top[0][c]) * fx;
+        let b = bottom[0][c] + (bottom[1][c] - bottom[0][c]) * fx;
+        out[c] = t + (b - t) * fy;
+    }
+    out
+}
+
+/*
+ * Curved CRT glass, pixels are pulled towards the centre the further out they are
+ */
+fn barrel(source: &Frame, amount: f32) -> Frame {
+    let mut out = Frame::new(source.width, source.height);
+    let (w, h) = (source.width as f32, source.height as f32);
+
+    for y in 0..source.height {
+        let v = (y as f32 + 0.5) / h * 2.0 - 1.0;
+        for x in 0..source.width {
+            let u = (x as f32 + 0.5) / w * 2.0 - 1.0;
+            let distortion = 1.0 + amount * (u * u + v * v);
+            let (su, sv) = (u * distortion, v * distortion);
+
+            if su.abs() > 1.0 || sv.abs() > 1.0 {
+                out.pixels[y * source.width + x] = pack(0, 0, 0);
+                continue;
+            }
+
+            let sx = ((su + 1.0) * 0.5 * w) as isize;
+            let sy = ((sv + 1.0) * 0.5 * h) as isize;
+            out.pixels[y * source.width + x] = source.get(sx, sy);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scale2x() {
+        let (on, off) = (pack(255, 255, 255), pack(0, 0, 0));
+        /*
+         * X .
+         * . X
+         */
+        let source = Frame {
+            width: 2,
+            height: 2,
+            pixels: vec![on, off, off, on],
+        };
+        let out = scale2x(&source);
+
+        assert_eq!((out.width, out.height), (4, 4));
+        //the empty top right cell fills in its corner between the two lit neighbours
+        assert_eq!(out.pixels[2], off);
+        assert_eq!(out.pixels[4 + 2], on);
+    }
+
+    #[test]
+    fn test_filter_size() {
+        let colors = vec![Color::RGB(0, 255, 0); 64 * 32];
+        for filter in FILTERS {
+            let frame = apply(filter, &colors, 64, 32, 320, 160);
+            assert_eq!(frame.pixels.len(), 320 * 160, "{}", filter);
+        }
+        assert_eq!("CRT".parse::<Filter>(), Ok(Filter::Crt));
+    }
+}
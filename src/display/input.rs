@@ -0,0 +1,343 @@
+use crate::chip8::Keypad;
+use crate::config::{self, Config};
+use sdl2::keyboard::Keycode;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/*
+ * Host keys bound to the CHIP-8 keypads
+ *
+ * A profile binds some or all of the 16 keys of one keypad. Several profiles can be
+ * active at once, so two players can share a keyboard, each on their own keys:
+ *
+ *     [default]
+ *     input = keyboard, player2
+ *
+ *     [input.player2]
+ *     keypad = 2
+ *     1 = Keypad 7
+ *     4 = Keypad 4
+ *
+ * `[input.<name>]` defines a profile or changes the keys of a built-in one, and
+ * `[rom.<sha1>.input.<name>]` does the same for a single ROM. Keys are SDL key names.
+ *
+ * The bindings screen saves to bindings.ini in the data directory, whose sections
+ * override the same sections of the config.
+ */
+pub const DEFAULT_PROFILES: &str = "keyboard";
+
+/*
+ * Keys the window handles itself, a game would never see them
+ */
+pub const HOTKEYS: [Keycode; 19] = [
+    Keycode::ESCAPE,
+    Keycode::TAB,
+    Keycode::F1,
+    Keycode::F2,
+    Keycode::F3,
+    Keycode::F4,
+    Keycode::F5,
+    Keycode::F6,
+    Keycode::F7,
+    Keycode::F8,
+    Keycode::F9,
+    Keycode::F10,
+    Keycode::F11,
+    Keycode::F12,
+    Keycode::PAGEUP,
+    Keycode::PAGEDOWN,
+    Keycode::M,
+    Keycode::LEFTBRACKET,
+    Keycode::RIGHTBRACKET,
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+    pub name: String,
+    pub keypad: Keypad,
+    /// The host key for each CHIP-8 key
+    pub keys: [Option<Keycode>; 16],
+}
+
+/*
+ *     SDL2                CHIP-8
+ * [1][2][3][4]         [1][2][3][C]
+ * [Q][W][E][R]   =>    [4][5][6][D]
+ * [A][S][D][F]   =>    [7][8][9][E]
+ * [Z][X][C][V]         [A][0][B][F]
+ */
+const KEYBOARD: [Keycode; 16] = [
+    Keycode::X,
+    Keycode::NUM_1,
+    Keycode::NUM_2,
+    Keycode::NUM_3,
+    Keycode::Q,
+    Keycode::W,
+    Keycode::E,
+    Keycode::A,
+    Keycode::S,
+    Keycode::D,
+    Keycode::Z,
+    Keycode::C,
+    Keycode::NUM_4,
+    Keycode::R,
+    Keycode::F,
+    Keycode::V,
+];
+
+/*
+ * The same positions on the numeric keypad
+ * [7][8][9][/]
+ * [4][5][6][*]
+ * [1][2][3][-]
+ * [0][.][Enter][+]
+ */
+const NUMPAD: [Keycode; 16] = [
+    Keycode::KP_PERIOD,
+    Keycode::KP_7,
+    Keycode::KP_8,
+    Keycode::KP_9,
+    Keycode::KP_4,
+    Keycode::KP_5,
+    Keycode::KP_6,
+    Keycode::KP_1,
+    Keycode::KP_2,
+    Keycode::KP_3,
+    Keycode::KP_0,
+    Keycode::KP_ENTER,
+    Keycode::KP_DIVIDE,
+    Keycode::KP_MULTIPLY,
+    Keycode::KP_MINUS,
+    Keycode::KP_PLUS,
+];
+
+impl Profile {
+    pub fn new(name: &str, keypad: Keypad) -> Self {
+        Self {
+            name: name.to_string(),
+            keypad,
+            keys: [None; 16],
+        }
+    }
+
+    pub fn builtin(name: &str) -> Option<Self> {
+        let keys = match name {
+            "keyboard" => KEYBOARD,
+            "numpad" => NUMPAD,
+            _ => return None,
+        };
+        Some(Self {
+            keys: keys.map(Some),
+            ..Self::new(name, Keypad::First)
+        })
+    }
+
+    pub fn section(rom_hash: Option<&str>, name: &str) -> String {
+        match rom_hash {
+            Some(hash) => format!("{}.input.{}", Config::rom_section(hash), name),
+            None => format!("input.{}", name),
+        }
+    }
+
+    /*
+     * The built-in profile of that name, or an empty one on the first keypad
+     */
+    pub fn base(name: &str) -> Self {
+        Self::builtin(name).unwrap_or_else(|| Self::new(name, Keypad::First))
+    }
+
+    /*
+     * The built-in profile of that name, changed by the global and then the ROM's section,
+     * each section from every config in turn
+     */
+    pub fn load(configs: &[&Config], rom_hash: Option<&str>, name: &str) -> Result<Self, String> {
+        let mut sections = vec![Self::section(None, name)];
+        sections.extend(rom_hash.map(|hash| Self::section(Some(hash), name)));
+
+        let defined = sections.iter().any(|section| {
+            configs
+                .iter()
+                .any(|config| config.section(section).is_some())
+        });
+        if Self::builtin(name).is_none() && !defined {
+            return Err(format!("Unknown input profile: {}", name));
+        }
+
+        let mut profile = Self::base(name);
+        for section in &sections {
+            for config in configs {
+                profile.apply(config, section)?;
+            }
+        }
+        Ok(profile)
+    }
+
+    /*
+     * Changes the keys set in a section, a missing section changes nothing
+     */
+    pub fn apply(&mut self, config: &Config, section: &str) -> Result<(), String> {
+        for (key, value) in config.section(section).unwrap_or_default() {
+            self.set(key, value)?;
+        }
+        Ok(())
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        if key == "keypad" {
+            self.keypad = match value {
+                "1" => Keypad::First,
+                "2" => Keypad::Second,
+                _ => return Err(format!("Keypad must be 1 or 2, not {}", value)),
+            };
+            return Ok(());
+        }
+
+        let button = match u8::from_str_radix(key, 16) {
+            Ok(button) if key.len() == 1 => button,
+            _ => {
+                return Err(format!(
+                    "Unknown key in input profile {}: {}",
+                    self.name, key
+                ));
+            }
+        };
+        self.keys[button as usize] = match value {
+            "" => None,
+            name => {
+                let key = Keycode::from_name(name).ok_or(format!("Unknown host key: {}", name))?;
+                if HOTKEYS.contains(&key) {
+                    return Err(format!(
+                        "{} is an emulator hotkey and can't be bound in input profile {}",
+                        name, self.name
+                    ));
+                }
+                Some(key)
+            }
+        };
+        Ok(())
+    }
+
+    /*
+     * Writes the keys that differ from `below`, the profile the section is applied over,
+     * and removes the rest. An unbound key is written empty.
+     */
+    pub fn save(&self, below: &Profile, config: &mut Config, rom_hash: Option<&str>) {
+        let section = Self::section(rom_hash, &self.name);
+        if self.keypad == below.keypad {
+            config.remove(&section, "keypad");
+        } else {
+            let keypad = match self.keypad {
+                Keypad::First => "1",
+                Keypad::Second => "2",
+            };
+            config.set(&section, "keypad", keypad);
+        }
+        for (button, (key, below)) in self.keys.iter().zip(below.keys).enumerate() {
+            let button = format!("{:X}", button);
+            if *key == below {
+                config.remove(&section, &button);
+            } else {
+                let name = key.map(|key| key.name()).unwrap_or_default();
+                config.set(&section, &button, &name);
+            }
+        }
+    }
+
+    pub fn button(&self, key: Keycode) -> Option<u8> {
+        self.keys
+            .iter()
+            .position(|bound| *bound == Some(key))
+            .map(|button| button as u8)
+    }
+}
+
+/*
+ * The active profiles, a host key bound in more than one goes to the first
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Input {
+    profiles: Vec<Profile>,
+}
+
+impl Default for Input {
+    fn default() -> Self {
+        Self::new(vec![Profile::builtin(DEFAULT_PROFILES).unwrap()])
+    }
+}
+
+impl Input {
+    pub fn new(profiles: Vec<Profile>) -> Self {
+        Self { profiles }
+    }
+
+    /*
+     * A comma separated list of profile names
+     */
+    pub fn load(configs: &[&Config], rom_hash: Option<&str>, names: &str) -> Result<Self, String> {
+        let profiles = names
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(|name| Profile::load(configs, rom_hash, name))
+            .collect::<Result<Vec<_>, _>>()?;
+        if profiles.is_empty() {
+            return Err("At least one input profile must be active".to_string());
+        }
+        Ok(Self::new(profiles))
+    }
+
+    pub fn profiles(&self) -> &[Profile] {
+        &self.profiles
+    }
+
+    pub fn button(&self, key: Keycode) -> Option<(Keypad, u8)> {
+        self.profiles
+            .iter()
+            .find_map(|profile| Some((profile.keypad, profile.button(key)?)))
+    }
+}
+
+pub fn bindings_path() -> PathBuf {
+    config::data_dir().join("bindings.ini")
+}
+
+/*
+ * The bindings saved from the bindings screen, kept apart from the config
+ * so the user's config file is never rewritten by the emulator
+ */
+pub fn load_bindings() -> io::Result<Config> {
+    match fs::read_to_string(bindings_path()) {
+        Ok(text) => Ok(Config::parse(&text)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Config::default()),
+        Err(e) => Err(e),
+    }
+}
+
+pub fn save_bindings(bindings: &Config) -> io::Result<()> {
+    let path = bindings_path();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, bindings.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_two_players() {
+        let config = Config::parse("[input.player2]\nkeypad = 2\n");
+        let input = Input::load(&[&config], None, "keyboard, numpad, player2").unwrap();
+
+        assert_eq!(input.button(Keycode::R), Some((Keypad::First, 0xD)));
+        assert_eq!(
+            input.button(Keycode::KP_MULTIPLY),
+            Some((Keypad::First, 0xD))
+        );
+        assert_eq!(input.button(Keycode::P), None);
+        assert_eq!(input.profiles()[2].keypad, Keypad::Second);
+        assert!(Input::load(&[&config], None, "keyboard, player3").is_err());
+    }
+}
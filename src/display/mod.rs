@@ -1,60 +1,158 @@
-use crate::audio::Audio;
-use crate::chip8::CPU;
-use crate::chip8::debugger::Propagate;
-use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
-use sdl2::pixels::Color;
-use sdl2::rect::Rect;
+use crate::audio::{Audio, BeeperConfig};
+use crate::capture::recorder::{AudioRecorder, RecordFormat, Recorder};
+use crate::capture::{self, Image};
+use crate::chip8::debugger::{self, Propagate};
+use crate::chip8::megachip::SampleCommand;
+use crate::chip8::timing::TimingModel;
+use crate::chip8::{CPU, Keypad, SoundEdge};
+use crate::config::Config;
+use crate::display::binding::{BindingResult, BindingScreen};
+use crate::display::filter::Filter;
+use crate::display::input::Input;
+use crate::display::osd::Osd;
+use crate::display::palette::Palette;
+use crate::display::phosphor::{Persistence, Phosphor};
+use crate::display::speed::Speed;
+use crate::display::texture::FrameTexture;
+use crate::display::window::WindowGeometry;
+use crate::rpl::RplStore;
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::{Keycode, Mod};
 use sdl2::render::Canvas;
-use sdl2::video::Window;
+use sdl2::video::{FullscreenType, Window};
 use sdl2::{EventPump, Sdl, VideoSubsystem};
 use std::error::Error;
 use std::time::Duration;
 
-const FADE_SPEED: u8 = 40;
+pub mod binding;
+pub mod filter;
+pub mod input;
+pub mod osd;
+pub mod palette;
+pub mod phosphor;
+pub mod speed;
+pub mod texture;
+pub mod window;
+
+const WINDOW_TITLE: &str = "Chip-8 Emulator";
+const RECORDING_SCALE: u32 = 4;
+const VOLUME_STEP: f32 = 0.05;
 
 pub struct Display {
     sdl2_context: Sdl,
-    height: u32,
-    width: u32,
+    geometry: WindowGeometry,
     video_subsystem: VideoSubsystem,
     audio: Audio,
-    color: Color,
-    pixel_decay: [u8; 64 * 32],
+    palette: Palette,
+    phosphor: Phosphor,
+    filter: Filter,
+    speed: Speed,
+    osd: Osd,
+    recorder: Option<Recorder>,
+    audio_recorder: Option<AudioRecorder>,
+    rpl: Option<RplStore>,
+    input: Input,
+    binding: Option<BindingScreen>,
+    resume_after_binding: bool,
+    rom_hash: Option<String>,
+    redraw: bool,
+    quit: bool,
 }
 
 impl Default for Display {
     fn default() -> Self {
-        Self::new(640, 320, Color::WHITE).unwrap()
+        Self::new(640, 320, Palette::default()).unwrap()
     }
 }
 
 impl Display {
-    pub fn new(width: u32, height: u32, color: Color) -> Result<Self, Box<dyn Error>> {
+    pub fn new(width: u32, height: u32, palette: Palette) -> Result<Self, Box<dyn Error>> {
         let sdl2_context = sdl2::init()?;
         let video_subsystem = sdl2_context.video()?;
-        let audio = Audio::new(&sdl2_context)?;
-        let pixel_decay = [0; 64 * 32];
+        let audio = Audio::new(&sdl2_context, BeeperConfig::default())?;
         Ok(Self {
             sdl2_context,
-            height,
-            width,
+            geometry: WindowGeometry::load(WindowGeometry::new(width, height)),
             video_subsystem,
             audio,
-            color,
-            pixel_decay,
+            palette,
+            phosphor: Phosphor::default(),
+            filter: Filter::default(),
+            speed: Speed::new(),
+            osd: Osd::new(),
+            recorder: None,
+            audio_recorder: None,
+            rpl: None,
+            input: Input::default(),
+            binding: None,
+            resume_after_binding: false,
+            rom_hash: None,
+            redraw: true,
+            quit: false,
         })
     }
 
-    fn event(&self, event_pump: &mut EventPump, cpu: &mut CPU) {
+    fn event(&mut self, event_pump: &mut EventPump, canvas: &mut Canvas<Window>, cpu: &mut CPU) {
         for event in event_pump.poll_iter() {
+            //anything from the window or the keyboard may change what is on screen
+            self.redraw = true;
+
+            /*
+             * The bindings screen takes every key press while it is open
+             */
+            if self.binding.is_some() {
+                if let Event::KeyDown {
+                    keycode: Some(key),
+                    repeat: false,
+                    ..
+                } = event
+                {
+                    self.binding_key(key);
+                }
+                if matches!(event, Event::KeyDown { .. }) {
+                    continue;
+                }
+            }
+
             match event {
                 Event::Quit { .. }
                 | Event::KeyDown {
                     keycode: Some(Keycode::Escape),
                     ..
+                } => self.quit = true,
+
+                /*
+                 * Remember where the window is, but not the size of the fullscreen mode
+                 */
+                Event::Window {
+                    win_event: WindowEvent::Moved(x, y),
+                    ..
+                } if !self.geometry.fullscreen => self.geometry.position = Some((x, y)),
+
+                Event::Window {
+                    win_event: WindowEvent::Resized(width, height),
+                    ..
+                } if !self.geometry.fullscreen => {
+                    self.geometry.width = width.max(64) as u32;
+                    self.geometry.height = height.max(32) as u32;
+                }
+
+                /*
+                 * F11 toggles fullscreen, Shift+F11 integer scaling
+                 */
+                Event::KeyDown {
+                    keycode: Some(Keycode::F11),
+                    keymod,
+                    repeat: false,
+                    ..
                 } => {
-                    std::process::exit(0);
+                    if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                        self.geometry.integer_scale = !self.geometry.integer_scale;
+                        self.apply_scaling(canvas);
+                    } else {
+                        self.geometry.fullscreen = !self.geometry.fullscreen;
+                        self.apply_fullscreen(canvas);
+                    }
                 }
 
                 Event::KeyDown {
@@ -67,17 +165,203 @@ impl Display {
                      */
                     if *cpu.debug.get_status() == Propagate::Disable {
                         cpu.debug.enable();
-                        println!("Debug Propagation Enabled");
+                        self.osd.message("Debug Propagation Enabled");
                     } else {
                         cpu.debug.disable();
-                        println!("Debug Propagation Disabled:");
+                        self.osd.message("Debug Propagation Disabled");
+                    }
+                }
+
+                /*
+                 * Speed controls
+                 * F2 pause/resume, F3 advance a single frame (pauses first)
+                 * F4 cycles slow motion, F5 cycles fast forward
+                 * PageUp/PageDown adjust the instructions executed per frame
+                 */
+                Event::KeyDown {
+                    keycode: Some(Keycode::F2),
+                    repeat: false,
+                    ..
+                } => {
+                    self.speed.toggle_pause();
+                    self.speed_message();
+                }
+
+                Event::KeyDown {
+                    keycode: Some(Keycode::F3),
+                    repeat: false,
+                    ..
+                } => {
+                    self.speed.advance_frame();
+                    self.speed_message();
+                }
+
+                Event::KeyDown {
+                    keycode: Some(Keycode::F4),
+                    repeat: false,
+                    ..
+                } => {
+                    self.speed.cycle_slow_motion();
+                    self.speed_message();
+                }
+
+                Event::KeyDown {
+                    keycode: Some(Keycode::F5),
+                    repeat: false,
+                    ..
+                } => {
+                    self.speed.cycle_fast_forward();
+                    self.speed_message();
+                }
+
+                Event::KeyDown {
+                    keycode: Some(Keycode::PAGEUP),
+                    repeat: false,
+                    ..
+                } => {
+                    self.speed.increase_instructions();
+                    self.speed_message();
+                }
+
+                Event::KeyDown {
+                    keycode: Some(Keycode::PAGEDOWN),
+                    repeat: false,
+                    ..
+                } => {
+                    self.speed.decrease_instructions();
+                    self.speed_message();
+                }
+
+                /*
+                 * On-screen display panels
+                 * F6 toggles the register and timer panel, F7 the keypad map
+                 */
+                Event::KeyDown {
+                    keycode: Some(Keycode::F6),
+                    repeat: false,
+                    ..
+                } => {
+                    self.osd.toggle_registers();
+                }
+
+                Event::KeyDown {
+                    keycode: Some(Keycode::F7),
+                    repeat: false,
+                    ..
+                } => {
+                    self.osd.toggle_keypad();
+                }
+
+                /*
+                 * Tab opens the key bindings screen, the machine is paused while it is open
+                 */
+                Event::KeyDown {
+                    keycode: Some(Keycode::TAB),
+                    repeat: false,
+                    ..
+                } => {
+                    self.resume_after_binding = !self.speed.is_paused();
+                    self.speed.pause();
+                    self.binding = Some(BindingScreen::new(&self.input, self.rom_hash.as_deref()));
+                }
+
+                /*
+                 * F12 saves a screenshot as rendered, Shift+F12 the raw frame buffer bitmap
+                 */
+                Event::KeyDown {
+                    keycode: Some(Keycode::F12),
+                    keymod,
+                    repeat: false,
+                    ..
+                } => {
+                    let raw = keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD);
+                    self.screenshot(canvas, cpu, raw);
+                }
+
+                /*
+                 * F8 cycles through the palette presets, Shift+F8 the post-processing filters
+                 */
+                Event::KeyDown {
+                    keycode: Some(Keycode::F8),
+                    keymod,
+                    repeat: false,
+                    ..
+                } => {
+                    if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                        self.filter = self.filter.next();
+                        self.osd.message(format!("Filter {}", self.filter));
+                    } else {
+                        self.cycle_palette();
+                    }
+                }
+
+                /*
+                 * F10 cycles the phosphor persistence, Shift+F10 toggles present on draw
+                 */
+                Event::KeyDown {
+                    keycode: Some(Keycode::F10),
+                    keymod,
+                    repeat: false,
+                    ..
+                } => {
+                    if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                        let enabled = !self.phosphor.present_on_draw();
+                        self.phosphor.set_present_on_draw(enabled);
+                        cpu.set_present_on_draw(enabled);
+                        self.osd.message(format!(
+                            "Present On Draw {}",
+                            if enabled { "On" } else { "Off" }
+                        ));
+                    } else {
+                        let persistence = self.phosphor.persistence().next();
+                        self.phosphor.set_persistence(persistence);
+                        self.osd.message(format!("Persistence {}", persistence));
+                    }
+                }
+
+                /*
+                 * F9 starts and stops a GIF recording, Shift+F9 a numbered PNG sequence
+                 */
+                Event::KeyDown {
+                    keycode: Some(Keycode::F9),
+                    keymod,
+                    repeat: false,
+                    ..
+                } => {
+                    let sequence = keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD);
+                    self.toggle_recording(sequence);
+                }
+
+                /*
+                 * M mutes the beeper, [ and ] lower and raise the volume
+                 */
+                Event::KeyDown {
+                    keycode: Some(Keycode::M),
+                    repeat: false,
+                    ..
+                } => {
+                    if self.audio.toggle_mute() {
+                        self.osd.message("Muted");
+                    } else {
+                        self.osd.message("Unmuted");
                     }
                 }
 
+                Event::KeyDown {
+                    keycode: Some(Keycode::LEFTBRACKET),
+                    ..
+                } => self.change_volume(-VOLUME_STEP),
+
+                Event::KeyDown {
+                    keycode: Some(Keycode::RIGHTBRACKET),
+                    ..
+                } => self.change_volume(VOLUME_STEP),
+
                 /*
                  * Chip-8 Controls
                  * These controls go from 1-0 and A-F
-                 * They are to be translated from SDL's input from
+                 * They are translated from SDL's input by the active input profiles,
+                 * by default
                  *     SDL2                CHIP-8
                  * [1][2][3][4]         [1][2][3][C]
                  * [Q][W][E][R]   =>    [4][5][6][D]
@@ -87,8 +371,8 @@ impl Display {
                 Event::KeyDown {
                     keycode: Some(key), ..
                 } => {
-                    if let Some(key) = self.key2btn(key) {
-                        cpu.keypad[key as usize] = true;
+                    if let Some((keypad, key)) = self.key2btn(key) {
+                        cpu.press_key(keypad, key);
                         //println!("Key: 0x{:X}", key)
                     }
                 }
@@ -96,8 +380,8 @@ impl Display {
                 Event::KeyUp {
                     keycode: Some(key), ..
                 } => {
-                    if let Some(key) = self.key2btn(key) {
-                        cpu.keypad[key as usize] = false;
+                    if let Some((keypad, key)) = self.key2btn(key) {
+                        cpu.release_key(keypad, key);
                     }
                 }
                 _ => {}
@@ -105,89 +389,353 @@ impl Display {
         }
     }
 
-    fn key2btn(&self, key: Keycode) -> Option<u16> {
-        /*
-         * Keys that are registered must be converted to their respective u16 for the Chip-8 CPU
-         *
-         * In the future these set of key bindings will be custom by user configuration
-         */
-        match key {
-            Keycode::NUM_1 => Some(0x1),
-            Keycode::NUM_2 => Some(0x2),
-            Keycode::NUM_3 => Some(0x3),
-            Keycode::NUM_4 => Some(0xC),
-            Keycode::Q => Some(0x4),
-            Keycode::W => Some(0x5),
-            Keycode::E => Some(0x6),
-            Keycode::R => Some(0xD),
-            Keycode::A => Some(0x7),
-            Keycode::S => Some(0x8),
-            Keycode::D => Some(0x9),
-            Keycode::F => Some(0xE),
-            Keycode::Z => Some(0xA),
-            Keycode::X => Some(0x0),
-            Keycode::C => Some(0xB),
-            Keycode::V => Some(0xF),
-            _ => None,
-        }
-    }
-
-    fn render(&mut self, canvas: &mut Canvas<Window>, cpu: &CPU) -> Result<(), Box<dyn Error>> {
-        canvas.set_draw_color(Color::BLACK);
-        canvas.clear();
-        canvas.set_draw_color(self.color);
+    fn binding_key(&mut self, key: Keycode) {
+        let Some(screen) = self.binding.as_mut() else {
+            return;
+        };
+        let for_rom = match screen.key(key) {
+            None => return,
+            Some(BindingResult::Cancel) => {
+                self.osd.message("Key Bindings Unchanged");
+                self.close_binding();
+                return;
+            }
+            Some(BindingResult::Save(for_rom)) => for_rom,
+        };
+
+        let saved = Config::load()
+            .map_err(|e| e.to_string())
+            .and_then(|config| {
+                let mut bindings = input::load_bindings().map_err(|e| e.to_string())?;
+                screen.save(&config, &mut bindings, for_rom)?;
+                input::save_bindings(&bindings).map_err(|e| e.to_string())?;
+                screen.reload(&[&config, &bindings])
+            });
+        match saved {
+            Ok(input) => {
+                self.input = input;
+                self.osd.message(if for_rom {
+                    "Key Bindings Saved For This ROM"
+                } else {
+                    "Key Bindings Saved"
+                });
+                self.close_binding();
+            }
+            Err(e) => self
+                .osd
+                .message(format!("Saving the key bindings failed: {}", e)),
+        }
+    }
+
+    fn close_binding(&mut self) {
+        self.binding = None;
+        if self.resume_after_binding {
+            self.speed.toggle_pause();
+        }
+    }
+
+    /*
+     * Show a transient message on the on-screen display
+     */
+    pub fn message(&mut self, text: impl Into<String>) {
+        self.osd.message(text);
+    }
+
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+    }
 
-        for (i, pixel) in cpu.frame_buffer.iter().enumerate() {
-            if *pixel {
-                self.pixel_decay[i] = 255;
-            } else if self.pixel_decay[i] > 0 {
-                self.pixel_decay[i] = self.pixel_decay[i].saturating_sub(FADE_SPEED);
+    fn cycle_palette(&mut self) {
+        let next = self.palette.preset_index().map_or(0, |i| i + 1);
+        self.palette = Palette::preset(next);
+        self.osd.message(format!("Palette {}", self.palette.name));
+    }
+
+    fn apply_fullscreen(&mut self, canvas: &mut Canvas<Window>) {
+        let mode = if self.geometry.fullscreen {
+            FullscreenType::Desktop
+        } else {
+            FullscreenType::Off
+        };
+        match canvas.window_mut().set_fullscreen(mode) {
+            Ok(()) if self.geometry.fullscreen => self.osd.message("Fullscreen"),
+            Ok(()) => self.osd.message("Windowed"),
+            Err(e) => {
+                self.geometry.fullscreen = !self.geometry.fullscreen;
+                self.osd.message(format!("Fullscreen failed: {}", e));
             }
+        }
+    }
 
-            //get the x and y from the 1D array frame buffer
-            let x = i % 64;
-            let y = i / 64;
+    /*
+     * The logical size keeps the display's aspect ratio and letterboxes the rest of the window,
+     * integer scaling additionally rounds the scale down to a whole number
+     */
+    fn apply_scaling(&mut self, canvas: &mut Canvas<Window>) {
+        match canvas.set_integer_scale(self.geometry.integer_scale) {
+            Ok(()) if self.geometry.integer_scale => self.osd.message("Integer Scaling On"),
+            Ok(()) => self.osd.message("Integer Scaling Off"),
+            Err(e) => self.osd.message(format!("Integer scaling failed: {}", e)),
+        }
+    }
 
-            let rect = Rect::new(x as i32, y as i32, 1, 1);
+    pub fn set_filter(&mut self, filter: Filter) {
+        self.filter = filter;
+    }
 
-            /*
-             * simulate oscilating fade from the 1980s
-             * with phosphorus Television
-             */
-            if self.pixel_decay[i] > 0 {
-                let brightness = self.pixel_decay[i] as f32 / 255.0;
-                let (r, g, b, a) = self.color.rgba();
-
-                let color = Color::RGBA(
-                    (r as f32 * brightness) as u8,
-                    (g as f32 * brightness) as u8,
-                    (b as f32 * brightness) as u8,
-                    (a as f32 * brightness) as u8,
-                );
-                canvas.set_draw_color(color);
-                canvas.fill_rect(rect)?;
+    /*
+     * The bindings screen saves per ROM under `rom_hash`
+     */
+    pub fn set_input(&mut self, input: Input, rom_hash: &str) {
+        self.input = input;
+        self.rom_hash = Some(rom_hash.to_string());
+    }
+
+    pub fn set_rpl_store(&mut self, store: RplStore) {
+        self.rpl = Some(store);
+    }
+
+    pub fn set_persistence(&mut self, persistence: Persistence, present_on_draw: bool) {
+        self.phosphor.set_persistence(persistence);
+        self.phosphor.set_present_on_draw(present_on_draw);
+    }
+
+    pub fn set_beeper(&mut self, config: BeeperConfig) {
+        self.audio.set_config(config);
+    }
+
+    fn change_volume(&mut self, delta: f32) {
+        self.audio.set_volume(self.audio.volume() + delta);
+        let percent = (self.audio.volume() * 100.0).round();
+        if self.audio.is_muted() {
+            self.osd.message(format!("Volume {}% (Muted)", percent));
+        } else {
+            self.osd.message(format!("Volume {}%", percent));
+        }
+    }
+
+    fn speed_message(&mut self) {
+        let label = self.speed.label();
+        self.osd.message(label);
+    }
+
+    /*
+     * Save the current frame to a timestamped PNG in the working directory
+     * the scaled screenshot is as large as the picture currently fits in the window
+     */
+    fn screenshot(&mut self, canvas: &Canvas<Window>, cpu: &CPU, raw: bool) {
+        let path = capture::timestamped_path("screenshot", "png");
+        let result = if raw {
+            capture::screenshot(&cpu.frame_buffer, &path)
+        } else {
+            let (width, height) = canvas.output_size().unwrap_or((0, 0));
+            let scale = (width / self.phosphor.width().max(1) as u32)
+                .min(height / self.phosphor.height().max(1) as u32)
+                .max(1);
+            capture::screenshot_scaled(&self.phosphor, &self.palette, scale, &path)
+        };
+
+        match result {
+            Ok(()) => self.osd.message(format!("Saved {}", path.display())),
+            Err(e) => self.osd.message(format!("Screenshot failed: {}", e)),
+        }
+    }
+
+    /*
+     * Video and audio are recorded together, the WAV is written next to the
+     * GIF or frame directory with the same name
+     */
+    fn toggle_recording(&mut self, sequence: bool) {
+        if let Some(recorder) = self.recorder.take() {
+            let path = recorder.path().display().to_string();
+            let audio = self.audio_recorder.take().map(|r| r.finish());
+
+            match (recorder.finish(), audio.transpose()) {
+                (Ok(frames), Ok(_)) => self
+                    .osd
+                    .message(format!("Recorded {} frames to {}", frames, path)),
+                (Err(e), _) | (_, Err(e)) => self.osd.message(format!("Recording failed: {}", e)),
+            }
+            return;
+        }
+
+        let (format, path) = if sequence {
+            (
+                RecordFormat::PngSequence,
+                capture::timestamped_path("recording", "frames"),
+            )
+        } else {
+            (
+                RecordFormat::Gif,
+                capture::timestamped_path("recording", "gif"),
+            )
+        };
+        let audio_path = path.with_extension("wav");
+
+        match Recorder::new(format, path)
+            .and_then(|r| Ok((r, AudioRecorder::new(audio_path, self.audio.config())?)))
+        {
+            Ok((recorder, audio_recorder)) => {
+                self.osd.message("Recording Started");
+                self.recorder = Some(recorder);
+                self.audio_recorder = Some(audio_recorder);
+            }
+            Err(e) => self.osd.message(format!("Recording failed: {}", e)),
+        }
+    }
+
+    /*
+     * Recordings capture every emulated frame as rendered, before the OSD is drawn on top,
+     * so pausing adds nothing and fast forward keeps every frame. Frames the picture
+     * did not change on repeat the last one.
+     */
+    fn record_frame(&mut self, changed: bool) {
+        let Some(recorder) = self.recorder.as_mut() else {
+            return;
+        };
+        if !changed && recorder.frames() > 0 {
+            if let Err(e) = recorder.repeat() {
+                self.recording_failed(e);
             }
+            return;
+        }
+
+        let image = Image::from_colors(
+            &self.phosphor.colors(&self.palette),
+            self.phosphor.width() as u32,
+            self.phosphor.height() as u32,
+            RECORDING_SCALE,
+        );
+        if let Err(e) = recorder.capture(&image) {
+            self.recording_failed(e);
+        }
+    }
+
+    /*
+     * The WAV follows emulated frames like the headless recorder, so pausing adds no
+     * silence and fast forward keeps every frame's audio. It is recorded as heard,
+     * at the current volume and silent while muted.
+     */
+    fn record_audio(&mut self, edges: &[SoundEdge], commands: &[SampleCommand]) {
+        let Some(audio_recorder) = self.audio_recorder.as_mut() else {
+            return;
+        };
+        audio_recorder.set_volume(self.audio.output_volume());
+        if let Err(e) = audio_recorder.frame(edges, commands) {
+            self.recording_failed(e);
+        }
+    }
+
+    fn recording_failed(&mut self, e: std::io::Error) {
+        self.recorder = None;
+        self.audio_recorder = None;
+        self.osd.message(format!("Recording failed: {}", e));
+    }
+
+    /*
+     * The keypad and CHIP-8 key a host key is bound to by the active input profiles
+     */
+    fn key2btn(&self, key: Keycode) -> Option<(Keypad, u8)> {
+        self.input.button(key)
+    }
+
+    /*
+     * The phosphor levels become one pixel buffer, uploaded with a single texture update.
+     * Unfiltered frames stay at the CHIP-8's resolution and are stretched by the renderer,
+     * filters work at the size the display actually covers on screen.
+     *
+     * Host frames where neither the picture nor anything over it changed are not presented.
+     */
+    fn render<T>(
+        &mut self,
+        canvas: &mut Canvas<Window>,
+        texture: &mut FrameTexture<T>,
+        cpu: &mut CPU,
+    ) -> Result<(), Box<dyn Error>> {
+        if !self.redraw && !self.osd.is_visible() && self.binding.is_none() {
+            return Ok(());
+        }
+        self.redraw = false;
+
+        canvas.set_draw_color(self.palette.background());
+        canvas.clear();
+
+        let (width, height) = (self.phosphor.width(), self.phosphor.height());
+        let (logical_width, logical_height) = logical_size(width, height);
+        if canvas.logical_size() != (logical_width, logical_height) {
+            canvas.set_logical_size(logical_width, logical_height)?;
+        }
+
+        let colors = self.phosphor.colors(&self.palette);
+        let frame = if self.filter == Filter::None {
+            filter::colorize(&colors, width, height)
+        } else {
+            let (scale, _) = canvas.scale();
+            filter::apply(
+                self.filter,
+                &colors,
+                width,
+                height,
+                ((logical_width as f32 * scale) as usize).max(logical_width as usize),
+                ((logical_height as f32 * scale) as usize).max(logical_height as usize),
+            )
+        };
+        canvas.copy(texture.upload(&frame)?, None, None)?;
+
+        self.osd.draw(canvas, cpu)?;
+        if let Some(screen) = &self.binding {
+            self.osd.draw_binding(canvas, screen)?;
         }
+
         canvas.present();
         Ok(())
     }
 
+    /*
+     * Runs until the window is closed, the window geometry is saved however the run ends
+     */
     pub fn run(&mut self, cpu: &mut CPU) -> Result<(), Box<dyn Error>> {
-        let window = self
-            .video_subsystem
-            .window("Chip-8 Emulator", self.width, self.height)
-            .position_centered()
-            .build()?;
+        let result = self.run_window(cpu);
+        if let Err(e) = self.geometry.save() {
+            eprintln!("Could not save the window geometry: {}", e);
+        }
+        result
+    }
+
+    fn run_window(&mut self, cpu: &mut CPU) -> Result<(), Box<dyn Error>> {
+        self.speed
+            .set_cycle_timing(cpu.timing == TimingModel::Vip || cpu.is_vip());
+
+        let mut builder =
+            self.video_subsystem
+                .window(WINDOW_TITLE, self.geometry.width, self.geometry.height);
+        match self.geometry.position {
+            Some((x, y)) => builder.position(x, y),
+            None => builder.position_centered(),
+        };
+        let mut window = builder.resizable().build()?;
+        window.set_minimum_size(64, 32)?;
 
         let mut canvas = window.into_canvas().build()?;
 
         canvas.set_logical_size(64, 32)?;
+        canvas.set_integer_scale(self.geometry.integer_scale)?;
+        if self.geometry.fullscreen {
+            self.apply_fullscreen(&mut canvas);
+        }
+
+        let texture_creator = canvas.texture_creator();
+        let mut texture = FrameTexture::new(&texture_creator);
 
         let mut event_pump = self.sdl2_context.event_pump()?;
         let target_frame_duration = Duration::from_nanos(1_000_000_000u64 / 60);
+        let mut title = String::new();
         loop {
             let frame_start = std::time::Instant::now();
-            self.event(&mut event_pump, cpu);
+            self.event(&mut event_pump, &mut canvas, cpu);
+            if self.quit {
+                return Ok(());
+            }
 
             /*
              * the Chip-8 CPU logic reads from a Read Only Memory file
@@ -199,23 +747,28 @@ impl Display {
              * if I want to Implement swappable ROM into the CHIP-8 design
              * there will need to be a gui layer that acts as a virtual "Cartridge" design
              */
+            for _ in 0..self.speed.frames_this_tick() {
+                if !self.emulate_frame(cpu) {
+                    break;
+                }
+            }
 
             /*
-             * tick for 10x the sleep thread framerate 60fps * 10
+             * Uncapped fast forward keeps emulating until this host frame's budget is spent
              */
-            for _ in 0..10 {
-                cpu.run();
+            if self.speed.is_uncapped() {
+                while frame_start.elapsed() < target_frame_duration && self.emulate_frame(cpu) {}
             }
 
-            cpu.update_timers();
+            self.audio.set_hold(self.speed.is_paused());
 
-            if cpu.get_sound_timer() > 0 {
-                self.audio.device.resume();
-            } else {
-                self.audio.device.pause();
-            }
+            self.render(&mut canvas, &mut texture, cpu)?;
 
-            self.render(&mut canvas, cpu)?;
+            let label = format!("{} | {}", WINDOW_TITLE, self.speed.label());
+            if label != title {
+                canvas.window_mut().set_title(&label)?;
+                title = label;
+            }
 
             let elapsed_time = frame_start.elapsed();
             if elapsed_time < target_frame_duration {
@@ -223,4 +776,40 @@ impl Display {
             }
         }
     }
+
+    /*
+     * A machine error breaks into the debugger, the machine is paused on the failing
+     * instruction and the call stack is printed. False if the frame did not complete.
+     */
+    fn emulate_frame(&mut self, cpu: &mut CPU) -> bool {
+        if let Err(e) = cpu.run_frame(self.speed.instructions_per_frame()) {
+            self.speed.pause();
+            self.osd.message(e.to_string());
+            eprintln!("{}\n{}", e, debugger::backtrace(&cpu.backtrace()));
+            return false;
+        }
+        let changed = self.phosphor.update(cpu.take_picture());
+        self.redraw |= changed;
+        self.record_frame(changed);
+        self.record_audio(cpu.sound_edges(), cpu.sample_commands());
+
+        self.audio.push_frame(cpu.sound_edges());
+        self.audio.play_samples(cpu.sample_commands());
+        if let Some(Err(e)) = self.rpl.as_mut().map(|rpl| rpl.sync(cpu.rpl_flags())) {
+            self.osd
+                .message(format!("Saving the RPL flags failed: {}", e));
+        }
+        true
+    }
+}
+
+/*
+ * MegaChip's 256x192 is shown at 4:3, every other mode fills the VIP's 2:1 screen
+ */
+fn logical_size(width: usize, height: usize) -> (u32, u32) {
+    if width * 3 == height * 4 {
+        (width as u32, height as u32)
+    } else {
+        (64, 32)
+    }
 }
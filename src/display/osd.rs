@@ -0,0 +1,374 @@
+use crate::chip8::{CPU, Keypad};
+use crate::display::binding::BindingScreen;
+use sdl2::pixels::Color;
+use sdl2::rect::FRect;
+use sdl2::render::{BlendMode, Canvas};
+use sdl2::video::Window;
+use std::time::{Duration, Instant};
+
+/*
+ * On-screen display drawn over the game
+ *
+ * The OSD uses its own grid of 256 columns across the emulated display, independent of
+ * the CHIP-8 resolution, so text stays legible at any window size. Glyphs are 3x5 pixels
+ * with one pixel of spacing, giving 64 characters per line.
+ */
+const OSD_COLUMNS: f32 = 256.0;
+const GLYPH_WIDTH: f32 = 4.0;
+const GLYPH_HEIGHT: f32 = 6.0;
+const MESSAGE_DURATION: Duration = Duration::from_secs(2);
+const MAX_MESSAGES: usize = 4;
+
+const TEXT_COLOR: Color = Color::RGB(255, 255, 255);
+const PANEL_COLOR: Color = Color::RGBA(0, 0, 0, 180);
+const KEY_COLOR: Color = Color::RGBA(80, 80, 80, 220);
+const KEY_PRESSED_COLOR: Color = Color::RGB(255, 200, 0);
+
+/*
+ * The CHIP-8 keypad as laid out on the COSMAC VIP
+ */
+pub const KEYPAD_LAYOUT: [[u8; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+
+#[derive(Debug, Default)]
+pub struct Osd {
+    messages: Vec<(String, Instant)>,
+    show_registers: bool,
+    show_keypad: bool,
+}
+
+impl Osd {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /*
+     * Queue a transient message, the oldest one is dropped once the queue is full
+     */
+    pub fn message(&mut self, text: impl Into<String>) {
+        if self.messages.len() >= MAX_MESSAGES {
+            self.messages.remove(0);
+        }
+        self.messages
+            .push((text.into(), Instant::now() + MESSAGE_DURATION));
+    }
+
+    pub fn toggle_registers(&mut self) -> bool {
+        self.show_registers = !self.show_registers;
+        self.show_registers
+    }
+
+    pub fn toggle_keypad(&mut self) -> bool {
+        self.show_keypad = !self.show_keypad;
+        self.show_keypad
+    }
+
+    /*
+     * True while anything is drawn over the game, including messages about to expire
+     */
+    pub fn is_visible(&self) -> bool {
+        self.show_registers || self.show_keypad || !self.messages.is_empty()
+    }
+
+    pub fn draw(&mut self, canvas: &mut Canvas<Window>, cpu: &CPU) -> Result<(), String> {
+        let now = Instant::now();
+        self.messages.retain(|(_, expiry)| *expiry > now);
+
+        let (logical_width, logical_height) = canvas.logical_size();
+        let unit = logical_width as f32 / OSD_COLUMNS;
+        let rows = logical_height as f32 / unit;
+
+        canvas.set_blend_mode(BlendMode::Blend);
+
+        if self.show_registers {
+            let lines = Self::register_lines(cpu);
+            let width = lines.iter().map(|l| l.len()).max().unwrap_or(0) as f32 * GLYPH_WIDTH;
+            let height = lines.len() as f32 * GLYPH_HEIGHT;
+            Self::panel(canvas, unit, 0.0, 0.0, width + 2.0, height + 2.0)?;
+            for (i, line) in lines.iter().enumerate() {
+                Self::text(canvas, unit, 1.0, 1.0 + i as f32 * GLYPH_HEIGHT, line)?;
+            }
+        }
+
+        if self.show_keypad {
+            Self::keypad(canvas, unit, OSD_COLUMNS - 34.0, 0.0, &cpu.keypad)?;
+        }
+
+        for (i, (text, _)) in self.messages.iter().rev().enumerate() {
+            let y = rows - (i + 1) as f32 * (GLYPH_HEIGHT + 1.0) - 1.0;
+            let width = text.len() as f32 * GLYPH_WIDTH;
+            Self::panel(canvas, unit, 0.0, y - 1.0, width + 2.0, GLYPH_HEIGHT + 1.0)?;
+            Self::text(canvas, unit, 1.0, y, text)?;
+        }
+
+        canvas.set_blend_mode(BlendMode::None);
+        Ok(())
+    }
+
+    /*
+     * The key binding screen, the keypad with the host key bound to each key
+     * and the key waiting for one highlighted
+     */
+    pub fn draw_binding(
+        &self,
+        canvas: &mut Canvas<Window>,
+        screen: &BindingScreen,
+    ) -> Result<(), String> {
+        const CELL_WIDTH: f32 = 46.0;
+        const CELL_HEIGHT: f32 = 8.0;
+        const WIDTH: f32 = CELL_WIDTH * 4.0 + 14.0;
+        const X: f32 = (OSD_COLUMNS - WIDTH) / 2.0;
+        const Y: f32 = 4.0;
+
+        let (logical_width, _) = canvas.logical_size();
+        let unit = logical_width as f32 / OSD_COLUMNS;
+        let profile = screen.profile();
+        let keypad = match profile.keypad {
+            Keypad::First => 1,
+            Keypad::Second => 2,
+        };
+        let grid_y = Y + 2.0 + GLYPH_HEIGHT * 2.0;
+        let footer_y = grid_y + CELL_HEIGHT * 4.0 + 2.0;
+        let status = match screen.conflict() {
+            Some(conflict) => conflict.to_string(),
+            None => format!("Press a key for {:X}", screen.selected()),
+        };
+
+        canvas.set_blend_mode(BlendMode::Blend);
+        Self::panel(
+            canvas,
+            unit,
+            X,
+            Y,
+            WIDTH,
+            footer_y + GLYPH_HEIGHT * 3.0 + 2.0 - Y,
+        )?;
+        let title = format!("Key bindings: {} (keypad {})", profile.name, keypad);
+        Self::text(canvas, unit, X + 2.0, Y + 2.0, &title)?;
+
+        for (row, keys) in KEYPAD_LAYOUT.iter().enumerate() {
+            for (col, key) in keys.iter().enumerate() {
+                let cell_x = X + 2.0 + col as f32 * (CELL_WIDTH + 2.0);
+                let cell_y = grid_y + row as f32 * CELL_HEIGHT;
+
+                let color = if *key == screen.selected() {
+                    KEY_PRESSED_COLOR
+                } else {
+                    KEY_COLOR
+                };
+                canvas.set_draw_color(color);
+                canvas.fill_frect(Self::rect(
+                    unit,
+                    cell_x,
+                    cell_y,
+                    CELL_WIDTH,
+                    CELL_HEIGHT - 1.0,
+                ))?;
+
+                let host = profile.keys[*key as usize]
+                    .map(|k| k.name())
+                    .unwrap_or_default();
+                let label: String = format!("{:X} {}", key, host).chars().take(11).collect();
+                Self::text(canvas, unit, cell_x + 1.0, cell_y + 1.0, &label)?;
+            }
+        }
+
+        let lines = [
+            status.as_str(),
+            "Esc cancel  Backspace back  Delete unbind",
+            "F1 save  F2 save for this ROM  F3 next profile",
+        ];
+        for (i, line) in lines.iter().enumerate() {
+            Self::text(
+                canvas,
+                unit,
+                X + 2.0,
+                footer_y + i as f32 * GLYPH_HEIGHT,
+                line,
+            )?;
+        }
+        canvas.set_blend_mode(BlendMode::None);
+        Ok(())
+    }
+
+    fn register_lines(cpu: &CPU) -> Vec<String> {
+        let v = cpu.get_v_registers();
+        let mut lines: Vec<String> = v
+            .chunks(4)
+            .enumerate()
+            .map(|(row, chunk)| {
+                chunk
+                    .iter()
+                    .enumerate()
+                    .map(|(col, value)| format!("V{:X}:{:02X}", row * 4 + col, value))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect();
+
+        lines.push(format!(
+            "PC:{:04X} I:{:04X}",
+            cpu.get_pc(),
+            cpu.get_index_register()
+        ));
+        lines.push(format!(
+            "DT:{:02X} ST:{:02X}",
+            cpu.get_delay_timer(),
+            cpu.get_sound_timer()
+        ));
+        lines
+    }
+
+    /*
+     * 4x4 grid of keys, currently pressed keys are highlighted
+     */
+    fn keypad(
+        canvas: &mut Canvas<Window>,
+        unit: f32,
+        x: f32,
+        y: f32,
+        keypad: &[bool; 16],
+    ) -> Result<(), String> {
+        const CELL: f32 = 8.0;
+        Self::panel(canvas, unit, x, y, CELL * 4.0 + 2.0, CELL * 4.0 + 2.0)?;
+
+        for (row, keys) in KEYPAD_LAYOUT.iter().enumerate() {
+            for (col, key) in keys.iter().enumerate() {
+                let cell_x = x + 1.0 + col as f32 * CELL;
+                let cell_y = y + 1.0 + row as f32 * CELL;
+
+                let color = if keypad[*key as usize] {
+                    KEY_PRESSED_COLOR
+                } else {
+                    KEY_COLOR
+                };
+                canvas.set_draw_color(color);
+                canvas.fill_frect(Self::rect(unit, cell_x, cell_y, CELL - 1.0, CELL - 1.0))?;
+
+                let label = format!("{:X}", key);
+                Self::text(canvas, unit, cell_x + 2.0, cell_y + 1.0, &label)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn panel(
+        canvas: &mut Canvas<Window>,
+        unit: f32,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+    ) -> Result<(), String> {
+        canvas.set_draw_color(PANEL_COLOR);
+        canvas.fill_frect(Self::rect(unit, x, y, width, height))
+    }
+
+    /*
+     * Text is positioned on the OSD grid, one glyph pixel per grid unit
+     */
+    pub fn text(
+        canvas: &mut Canvas<Window>,
+        unit: f32,
+        x: f32,
+        y: f32,
+        text: &str,
+    ) -> Result<(), String> {
+        canvas.set_draw_color(TEXT_COLOR);
+
+        let mut rects = Vec::new();
+        for (i, c) in text.chars().enumerate() {
+            let glyph = glyph(c);
+            let glyph_x = x + i as f32 * GLYPH_WIDTH;
+
+            for (row, bits) in glyph.iter().enumerate() {
+                for col in 0..3 {
+                    if (bits >> (2 - col)) & 1 == 1 {
+                        rects.push(Self::rect(
+                            unit,
+                            glyph_x + col as f32,
+                            y + row as f32,
+                            1.0,
+                            1.0,
+                        ));
+                    }
+                }
+            }
+        }
+        canvas.fill_frects(&rects)
+    }
+
+    fn rect(unit: f32, x: f32, y: f32, width: f32, height: f32) -> FRect {
+        FRect::new(x * unit, y * unit, width * unit, height * unit)
+    }
+}
+
+/*
+ * 3x5 bitmap font, each row uses the lowest three bits
+ * lowercase letters share the uppercase glyphs
+ */
+pub fn glyph(c: char) -> [u8; 5] {
+    match c.to_ascii_uppercase() {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b010, 0b100, 0b100],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        '*' => [0b000, 0b101, 0b010, 0b101, 0b000],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        '|' => [0b010, 0b010, 0b010, 0b010, 0b010],
+        '%' => [0b101, 0b001, 0b010, 0b100, 0b101],
+        '(' => [0b001, 0b010, 0b010, 0b010, 0b001],
+        ')' => [0b100, 0b010, 0b010, 0b010, 0b100],
+        '[' => [0b011, 0b010, 0b010, 0b010, 0b011],
+        ']' => [0b110, 0b010, 0b010, 0b010, 0b110],
+        '=' => [0b000, 0b111, 0b000, 0b111, 0b000],
+        '!' => [0b010, 0b010, 0b010, 0b000, 0b010],
+        '?' => [0b111, 0b001, 0b010, 0b000, 0b010],
+        '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
+        '<' => [0b001, 0b010, 0b100, 0b010, 0b001],
+        '>' => [0b100, 0b010, 0b001, 0b010, 0b100],
+        ' ' => [0; 5],
+        _ => [0b111; 5],
+    }
+}
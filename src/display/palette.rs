@@ -0,0 +1,163 @@
+use sdl2::pixels::Color;
+use std::str::FromStr;
+
+/*
+ * Display colours
+ *
+ * `colors[0]` is the background and `colors[1]` the foreground. Multi-plane modes
+ * use all four: index 1 is plane 1 alone, 2 is plane 2 alone and 3 is both planes.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Palette {
+    pub name: String,
+    pub colors: [Color; 4],
+}
+
+const PRESETS: [(&str, [Color; 4]); 5] = [
+    (
+        "green",
+        [
+            Color::RGB(0x00, 0x00, 0x00),
+            Color::RGB(0x00, 0xFF, 0x00),
+            Color::RGB(0x00, 0x80, 0x00),
+            Color::RGB(0xAA, 0xFF, 0xAA),
+        ],
+    ),
+    (
+        "amber",
+        [
+            Color::RGB(0x00, 0x00, 0x00),
+            Color::RGB(0xFF, 0xB0, 0x00),
+            Color::RGB(0x80, 0x58, 0x00),
+            Color::RGB(0xFF, 0xDC, 0x8C),
+        ],
+    ),
+    (
+        "lcd",
+        [
+            Color::RGB(0xA8, 0xB5, 0x8E),
+            Color::RGB(0x26, 0x2D, 0x1F),
+            Color::RGB(0x6A, 0x76, 0x55),
+            Color::RGB(0x12, 0x16, 0x0E),
+        ],
+    ),
+    (
+        "octo",
+        [
+            Color::RGB(0x99, 0x66, 0x00),
+            Color::RGB(0xFF, 0xCC, 0x00),
+            Color::RGB(0xFF, 0x66, 0x00),
+            Color::RGB(0x66, 0x22, 0x00),
+        ],
+    ),
+    (
+        "contrast",
+        [
+            Color::RGB(0x00, 0x00, 0x00),
+            Color::RGB(0xFF, 0xFF, 0xFF),
+            Color::RGB(0x00, 0xFF, 0xFF),
+            Color::RGB(0xFF, 0xFF, 0x00),
+        ],
+    ),
+];
+
+/*
+ * The VP-590 colour board used by CHIP-8X, foreground colours by zone value
+ * and the four backgrounds 02A0 steps through
+ */
+pub const VP590_COLORS: [Color; 8] = [
+    Color::RGB(0x00, 0x00, 0x00),
+    Color::RGB(0xFF, 0x00, 0x00),
+    Color::RGB(0x00, 0x00, 0xFF),
+    Color::RGB(0xFF, 0x00, 0xFF),
+    Color::RGB(0x00, 0xFF, 0x00),
+    Color::RGB(0xFF, 0xFF, 0x00),
+    Color::RGB(0x00, 0xFF, 0xFF),
+    Color::RGB(0xFF, 0xFF, 0xFF),
+];
+pub const VP590_BACKGROUNDS: [Color; 4] = [
+    Color::RGB(0x00, 0x00, 0x80),
+    Color::RGB(0x00, 0x00, 0x00),
+    Color::RGB(0x00, 0x80, 0x00),
+    Color::RGB(0x80, 0x00, 0x00),
+];
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self::preset(0)
+    }
+}
+
+impl Palette {
+    /*
+     * Preset by index, wrapping around so callers can cycle through them
+     */
+    pub fn preset(index: usize) -> Self {
+        let (name, colors) = PRESETS[index % PRESETS.len()];
+        Self {
+            name: name.to_string(),
+            colors,
+        }
+    }
+
+    pub fn preset_index(&self) -> Option<usize> {
+        PRESETS.iter().position(|(name, _)| *name == self.name)
+    }
+
+    pub fn background(&self) -> Color {
+        self.colors[0]
+    }
+
+    pub fn foreground(&self) -> Color {
+        self.colors[1]
+    }
+}
+
+impl FromStr for Palette {
+    type Err = String;
+
+    /*
+     * Either a preset name, or two to four hex colours separated by spaces or commas:
+     * background, foreground, then the plane 2 and combined plane colours
+     */
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(index) = PRESETS
+            .iter()
+            .position(|(name, _)| name.eq_ignore_ascii_case(s))
+        {
+            return Ok(Self::preset(index));
+        }
+
+        let parsed = s
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|part| !part.is_empty())
+            .map(parse_color)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if !(2..=4).contains(&parsed.len()) {
+            return Err(format!("Unknown palette: {}", s));
+        }
+
+        let mut colors = [parsed[0], parsed[1], parsed[1], parsed[1]];
+        colors[2..parsed.len()].copy_from_slice(&parsed[2..]);
+
+        Ok(Self {
+            name: "custom".to_string(),
+            colors,
+        })
+    }
+}
+
+fn parse_color(s: &str) -> Result<Color, String> {
+    let hex = s.trim_start_matches('#');
+    if hex.len() != 6 {
+        return Err(format!("Invalid colour: {}", s));
+    }
+    let value = u32::from_str_radix(hex, 16).map_err(|_| format!("Invalid colour: {}", s))?;
+    Ok(Color::RGB(
+        (value >> 16) as u8,
+        (value >> 8) as u8,
+        value as u8,
+    ))
+}
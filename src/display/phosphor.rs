@@ -0,0 +1,284 @@
+use crate::chip8::framebuffer::{ColorLayer, FrameBuffer};
+use crate::display::palette::{Palette, VP590_BACKGROUNDS, VP590_COLORS};
+use sdl2::pixels::Color;
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+
+const DEFAULT_DECAY: f32 = 0.6;
+const DEFAULT_BLEND_FRAMES: usize = 3;
+const MAX_BLEND_FRAMES: usize = 8;
+
+/*
+ * How long a pixel stays visible after the CHIP-8 clears it
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Persistence {
+    /// Pixels show exactly what is in the frame buffer
+    Off,
+    /// Cleared pixels keep this fraction of their brightness every frame
+    Fade(f32),
+    /// A pixel is lit if it was lit in any of the last N frames, hides XOR flicker
+    Blend(usize),
+}
+
+impl Default for Persistence {
+    fn default() -> Self {
+        Self::Fade(DEFAULT_DECAY)
+    }
+}
+
+impl Persistence {
+    /*
+     * Off -> Fade -> Blend -> Off, used by the hotkey
+     */
+    pub fn next(self) -> Self {
+        match self {
+            Self::Off => Self::Fade(DEFAULT_DECAY),
+            Self::Fade(_) => Self::Blend(DEFAULT_BLEND_FRAMES),
+            Self::Blend(_) => Self::Off,
+        }
+    }
+}
+
+impl FromStr for Persistence {
+    type Err = String;
+
+    /*
+     * off, fade, fade:<decay 0-1>, blend or blend:<frames>
+     */
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (mode, value) = match s.trim().split_once(':') {
+            Some((mode, value)) => (mode, Some(value)),
+            None => (s.trim(), None),
+        };
+
+        match (mode.to_ascii_lowercase().as_str(), value) {
+            ("off", None) => Ok(Self::Off),
+            ("fade", None) => Ok(Self::Fade(DEFAULT_DECAY)),
+            ("fade", Some(decay)) => decay
+                .parse::<f32>()
+                .map(|d| Self::Fade(d.clamp(0.0, 0.99)))
+                .map_err(|e| format!("Invalid decay: {}", e)),
+            ("blend", None) => Ok(Self::Blend(DEFAULT_BLEND_FRAMES)),
+            ("blend", Some(frames)) => frames
+                .parse::<usize>()
+                .map(|n| Self::Blend(n.clamp(1, MAX_BLEND_FRAMES)))
+                .map_err(|e| format!("Invalid frame count: {}", e)),
+            _ => Err(format!("Unknown persistence mode: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Persistence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Off => write!(f, "off"),
+            Self::Fade(decay) => write!(f, "fade:{}", decay),
+            Self::Blend(frames) => write!(f, "blend:{}", frames),
+        }
+    }
+}
+
+/*
+ * simulate oscilating fade from the 1980s
+ * with phosphorus Television
+ *
+ * The phosphor turns frame buffers into per-pixel brightness levels. With
+ * `present_on_draw` it is only given complete pictures (see `CPU::take_picture`) and
+ * holds still on frames without one, faded pixels included.
+ */
+#[derive(Debug, Clone)]
+pub struct Phosphor {
+    width: usize,
+    height: usize,
+    levels: Vec<u8>,
+    persistence: Persistence,
+    present_on_draw: bool,
+    latched: Vec<bool>,
+    colors: Option<ColorLayer>,
+    direct: Option<Vec<u32>>,
+    history: VecDeque<Vec<bool>>,
+}
+
+impl Default for Phosphor {
+    fn default() -> Self {
+        Self::new(64, 32)
+    }
+}
+
+impl Phosphor {
+    pub fn new(width: usize, height: usize) -> Self {
+        let size = width * height;
+        Self {
+            width,
+            height,
+            levels: vec![0; size],
+            persistence: Persistence::default(),
+            present_on_draw: false,
+            latched: vec![false; size],
+            colors: None,
+            direct: None,
+            history: VecDeque::new(),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn persistence(&self) -> Persistence {
+        self.persistence
+    }
+
+    pub fn set_persistence(&mut self, persistence: Persistence) {
+        self.persistence = persistence;
+        self.history.clear();
+    }
+
+    pub fn present_on_draw(&self) -> bool {
+        self.present_on_draw
+    }
+
+    pub fn set_present_on_draw(&mut self, enabled: bool) {
+        self.present_on_draw = enabled;
+    }
+
+    /*
+     * Advance one displayed frame with the picture the CPU presented, if any.
+     * A change of resolution starts over with a blank screen.
+     *
+     * Returns false when the levels did not change, so there is nothing new to show.
+     */
+    pub fn update(&mut self, picture: Option<&FrameBuffer>) -> bool {
+        if let Some(picture) = picture {
+            if picture.width() != self.width || picture.height() != self.height {
+                let persistence = self.persistence;
+                let present_on_draw = self.present_on_draw;
+                *self = Self::new(picture.width(), picture.height());
+                self.persistence = persistence;
+                self.present_on_draw = present_on_draw;
+            }
+
+            for (latched, lit) in self.latched.iter_mut().zip(picture.lit()) {
+                *latched = lit;
+            }
+            self.colors = picture.colors().cloned();
+            self.direct = picture.direct_colors().map(<[u32]>::to_vec);
+        } else if self.present_on_draw {
+            return false;
+        }
+
+        let mut changed = picture.is_some();
+        match self.persistence {
+            Persistence::Off => {
+                for (level, pixel) in self.levels.iter_mut().zip(&self.latched) {
+                    *level = if *pixel { 255 } else { 0 };
+                }
+            }
+            Persistence::Fade(decay) => {
+                for (level, pixel) in self.levels.iter_mut().zip(&self.latched) {
+                    if *pixel {
+                        *level = 255;
+                    } else if *level > 0 {
+                        *level = (*level as f32 * decay) as u8;
+                        changed = true;
+                    }
+                }
+            }
+            Persistence::Blend(frames) => {
+                changed |= self.history.iter().any(|frame| *frame != self.latched);
+                self.history.push_front(self.latched.clone());
+                self.history.truncate(frames.max(1));
+
+                for (i, level) in self.levels.iter_mut().enumerate() {
+                    let lit = self.history.iter().any(|frame| frame[i]);
+                    *level = if lit { 255 } else { 0 };
+                }
+            }
+        }
+        changed
+    }
+
+    pub fn levels(&self) -> &[u8] {
+        &self.levels
+    }
+
+    /*
+     * Every pixel shaded by its level, in the palette's colours or, when the frame
+     * buffer had a colour layer, in the colours of the VP-590 board.
+     * Direct colours are shown as they are.
+     */
+    pub fn colors(&self, palette: &Palette) -> Vec<Color> {
+        if let Some(direct) = &self.direct {
+            return direct
+                .iter()
+                .map(|color| Color::RGB((color >> 16) as u8, (color >> 8) as u8, *color as u8))
+                .collect();
+        }
+
+        match &self.colors {
+            Some(colors) => {
+                let background = VP590_BACKGROUNDS[colors.background() as usize];
+                self.levels
+                    .iter()
+                    .enumerate()
+                    .map(|(i, level)| {
+                        let zone = colors.foreground(i % self.width, i / self.width);
+                        shade(background, VP590_COLORS[zone as usize], *level)
+                    })
+                    .collect()
+            }
+            None => self
+                .levels
+                .iter()
+                .map(|level| shade(palette.background(), palette.foreground(), *level))
+                .collect(),
+        }
+    }
+}
+
+/*
+ * Blend from the background to a colour by a phosphor level,
+ * 0 is the background and 255 the full colour
+ */
+pub fn shade(background: Color, color: Color, level: u8) -> Color {
+    let brightness = level as f32 / 255.0;
+    let mix = |from: u8, to: u8| (from as f32 + (to as f32 - from as f32) * brightness) as u8;
+
+    Color::RGBA(
+        mix(background.r, color.r),
+        mix(background.g, color.g),
+        mix(background.b, color.b),
+        mix(background.a, color.a),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fade() {
+        let mut phosphor = Phosphor::default();
+        let mut frame_buffer = FrameBuffer::default();
+        frame_buffer.set(0, 0, true);
+        assert!(phosphor.update(Some(&frame_buffer)));
+        frame_buffer.set(0, 0, false);
+        phosphor.update(Some(&frame_buffer));
+        phosphor.update(None);
+        //255 * 0.6 * 0.6
+        assert_eq!(phosphor.levels()[0], 91);
+        assert_eq!(phosphor.colors(&Palette::default()).len(), 64 * 32);
+
+        //present on draw holds the picture, fading included, until the next one
+        phosphor.set_present_on_draw(true);
+        assert!(!phosphor.update(None));
+        assert_eq!(phosphor.levels()[0], 91);
+        assert_eq!("fade:0.5".parse(), Ok(Persistence::Fade(0.5)));
+    }
+}
@@ -0,0 +1,235 @@
+/*
+ * Emulation speed controls for the SDL front end
+ *
+ * The run loop is paced to 60 host frames per second. Every emulated frame executes
+ * `instructions_per_frame` instructions followed by a single timer tick, so the speed
+ * mode decides how many emulated frames happen on each host frame.
+ */
+const DEFAULT_INSTRUCTIONS_PER_FRAME: u32 = 10;
+const MAX_INSTRUCTIONS_PER_FRAME: u32 = 1000;
+
+const FAST_FORWARD_STEPS: [u32; 4] = [2, 4, 8, 0];
+const SLOW_MOTION_STEPS: [u32; 3] = [2, 4, 8];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SpeedMode {
+    #[default]
+    Normal,
+    /// Emulated frames per host frame, `0` runs uncapped
+    FastForward(u32),
+    /// Host frames per emulated frame
+    SlowMotion(u32),
+}
+
+#[derive(Debug)]
+pub struct Speed {
+    mode: SpeedMode,
+    instructions_per_frame: u32,
+    cycle_timing: bool,
+    paused: bool,
+    advance: bool,
+    slow_counter: u32,
+}
+
+impl Default for Speed {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Speed {
+    pub fn new() -> Self {
+        Self {
+            mode: SpeedMode::Normal,
+            instructions_per_frame: DEFAULT_INSTRUCTIONS_PER_FRAME,
+            cycle_timing: false,
+            paused: false,
+            advance: false,
+            slow_counter: 0,
+        }
+    }
+
+    pub fn mode(&self) -> SpeedMode {
+        self.mode
+    }
+
+    pub fn instructions_per_frame(&self) -> u32 {
+        self.instructions_per_frame
+    }
+
+    /*
+     * Under a cycle timing model the instructions per frame are not used
+     */
+    pub fn set_cycle_timing(&mut self, enabled: bool) {
+        self.cycle_timing = enabled;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn is_uncapped(&self) -> bool {
+        !self.paused && self.mode == SpeedMode::FastForward(0)
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+        self.advance = false;
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        self.advance = false;
+    }
+
+    /*
+     * Frame advance only makes sense while paused, so it pauses the machine first
+     * and then lets exactly one emulated frame through on the next host frame
+     */
+    pub fn advance_frame(&mut self) {
+        self.paused = true;
+        self.advance = true;
+    }
+
+    /*
+     * Normal -> 2x -> 4x -> 8x -> uncapped -> Normal
+     */
+    pub fn cycle_fast_forward(&mut self) {
+        self.mode = match self.mode {
+            SpeedMode::FastForward(n) => match FAST_FORWARD_STEPS.iter().position(|&s| s == n) {
+                Some(i) if i + 1 < FAST_FORWARD_STEPS.len() => {
+                    SpeedMode::FastForward(FAST_FORWARD_STEPS[i + 1])
+                }
+                _ => SpeedMode::Normal,
+            },
+            _ => SpeedMode::FastForward(FAST_FORWARD_STEPS[0]),
+        };
+    }
+
+    /*
+     * Normal -> 1/2 -> 1/4 -> 1/8 -> Normal
+     */
+    pub fn cycle_slow_motion(&mut self) {
+        self.slow_counter = 0;
+        self.mode = match self.mode {
+            SpeedMode::SlowMotion(n) => match SLOW_MOTION_STEPS.iter().position(|&s| s == n) {
+                Some(i) if i + 1 < SLOW_MOTION_STEPS.len() => {
+                    SpeedMode::SlowMotion(SLOW_MOTION_STEPS[i + 1])
+                }
+                _ => SpeedMode::Normal,
+            },
+            _ => SpeedMode::SlowMotion(SLOW_MOTION_STEPS[0]),
+        };
+    }
+
+    pub fn increase_instructions(&mut self) {
+        let step = Self::instruction_step(self.instructions_per_frame);
+        self.instructions_per_frame =
+            (self.instructions_per_frame + step).min(MAX_INSTRUCTIONS_PER_FRAME);
+    }
+
+    pub fn decrease_instructions(&mut self) {
+        let step = Self::instruction_step(self.instructions_per_frame.saturating_sub(1));
+        self.instructions_per_frame = self.instructions_per_frame.saturating_sub(step).max(1);
+    }
+
+    fn instruction_step(ipf: u32) -> u32 {
+        match ipf {
+            0..10 => 1,
+            10..100 => 5,
+            _ => 50,
+        }
+    }
+
+    /*
+     * Number of emulated frames to run on the current host frame.
+     * Uncapped fast forward is handled by the run loop itself, which keeps
+     * emulating frames until the host frame budget is spent.
+     */
+    pub fn frames_this_tick(&mut self) -> u32 {
+        if self.paused {
+            let frames = self.advance as u32;
+            self.advance = false;
+            return frames;
+        }
+
+        match self.mode {
+            SpeedMode::Normal => 1,
+            SpeedMode::FastForward(n) => n.max(1),
+            SpeedMode::SlowMotion(n) => {
+                self.slow_counter += 1;
+                if self.slow_counter >= n {
+                    self.slow_counter = 0;
+                    1
+                } else {
+                    0
+                }
+            }
+        }
+    }
+
+    pub fn label(&self) -> String {
+        let speed = match self.mode {
+            SpeedMode::Normal => "1x".to_string(),
+            SpeedMode::FastForward(0) => "Uncapped".to_string(),
+            SpeedMode::FastForward(n) => format!("{}x", n),
+            SpeedMode::SlowMotion(n) => format!("1/{}x", n),
+        };
+
+        let rate = if self.cycle_timing {
+            "VIP Cycles".to_string()
+        } else {
+            format!("{} IPF", self.instructions_per_frame)
+        };
+
+        if self.paused {
+            format!("Paused | {} | {}", rate, speed)
+        } else {
+            format!("{} | {}", rate, speed)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fast_forward_and_slow_motion() {
+        let mut speed = Speed::new();
+        let steps: Vec<u32> = (0..5)
+            .map(|_| {
+                speed.cycle_fast_forward();
+                speed.frames_this_tick()
+            })
+            .collect();
+        assert_eq!(steps, [2, 4, 8, 1, 1]);
+        assert_eq!(speed.mode(), SpeedMode::Normal);
+
+        speed.cycle_slow_motion();
+        let ticks: Vec<u32> = (0..4).map(|_| speed.frames_this_tick()).collect();
+        assert_eq!(ticks, [0, 1, 0, 1]);
+
+        speed.advance_frame();
+        assert_eq!((speed.frames_this_tick(), speed.frames_this_tick()), (1, 0));
+    }
+
+    #[test]
+    fn test_instruction_steps() {
+        let mut speed = Speed::new();
+        speed.decrease_instructions();
+        assert_eq!(speed.instructions_per_frame(), 9);
+        speed.increase_instructions();
+        speed.increase_instructions();
+        assert_eq!(speed.instructions_per_frame(), 15);
+
+        for _ in 0..100 {
+            speed.increase_instructions();
+        }
+        assert_eq!(speed.instructions_per_frame(), MAX_INSTRUCTIONS_PER_FRAME);
+        for _ in 0..100 {
+            speed.decrease_instructions();
+        }
+        assert_eq!(speed.instructions_per_frame(), 1);
+    }
+}
@@ -0,0 +1,51 @@
+use crate::display::filter::Frame;
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::render::{Texture, TextureCreator, TextureValueError, UpdateTextureError};
+
+/*
+ * A streaming texture that follows the size of the frames uploaded to it
+ *
+ * The whole frame is written in one upload, replacing a draw call per pixel.
+ */
+pub struct FrameTexture<'a, T> {
+    creator: &'a TextureCreator<T>,
+    texture: Option<Texture<'a>>,
+}
+
+impl<'a, T> FrameTexture<'a, T> {
+    pub fn new(creator: &'a TextureCreator<T>) -> Self {
+        Self {
+            creator,
+            texture: None,
+        }
+    }
+
+    /*
+     * Upload `frame`, recreating the texture first if its size changed
+     */
+    pub fn upload(&mut self, frame: &Frame) -> Result<&Texture<'a>, String> {
+        let (width, height) = (frame.width as u32, frame.height as u32);
+        let stale = self.texture.as_ref().is_none_or(|texture| {
+            let query = texture.query();
+            query.width != width || query.height != height
+        });
+        if stale {
+            self.texture = Some(
+                self.creator
+                    .create_texture_streaming(PixelFormatEnum::ARGB8888, width, height)
+                    .map_err(|e: TextureValueError| e.to_string())?,
+            );
+        }
+
+        let texture = self.texture.as_mut().ok_or("No texture")?;
+        let bytes: Vec<u8> = frame
+            .pixels
+            .iter()
+            .flat_map(|pixel| pixel.to_ne_bytes())
+            .collect();
+        texture
+            .update(None, &bytes, frame.width * 4)
+            .map_err(|e: UpdateTextureError| e.to_string())?;
+        Ok(texture)
+    }
+}
@@ -0,0 +1,103 @@
+use crate::config::{self, Config};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+const SECTION: &str = "window";
+
+/*
+ * Window size, position and scaling mode, remembered between runs
+ *
+ * Kept in window.ini next to the config rather than in it, so the user's
+ * config file is not rewritten every time the window moves.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowGeometry {
+    /// None centres the window
+    pub position: Option<(i32, i32)>,
+    pub width: u32,
+    pub height: u32,
+    pub fullscreen: bool,
+    pub integer_scale: bool,
+}
+
+impl WindowGeometry {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            position: None,
+            width,
+            height,
+            fullscreen: false,
+            integer_scale: false,
+        }
+    }
+
+    pub fn path() -> PathBuf {
+        config::data_dir().join("window.ini")
+    }
+
+    /*
+     * The saved geometry, or `default` when nothing was saved yet
+     */
+    pub fn load(default: Self) -> Self {
+        match fs::read_to_string(Self::path()) {
+            Ok(text) => Self::parse(&Config::parse(&text), default),
+            Err(_) => default,
+        }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let path = Self::path();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, self.to_config().to_string())
+    }
+
+    fn parse(config: &Config, default: Self) -> Self {
+        let number = |key: &str| config.get_in(SECTION, key).and_then(|v| v.parse().ok());
+        let flag = |key: &str| config.get_in(SECTION, key).map(|v| v == "true");
+
+        Self {
+            position: number("x").zip(number("y")),
+            width: number("width").map_or(default.width, |w: i32| w.max(64) as u32),
+            height: number("height").map_or(default.height, |h: i32| h.max(32) as u32),
+            fullscreen: flag("fullscreen").unwrap_or(default.fullscreen),
+            integer_scale: flag("integer_scale").unwrap_or(default.integer_scale),
+        }
+    }
+
+    fn to_config(self) -> Config {
+        let mut config = Config::default();
+        if let Some((x, y)) = self.position {
+            config.set(SECTION, "x", &x.to_string());
+            config.set(SECTION, "y", &y.to_string());
+        }
+        config.set(SECTION, "width", &self.width.to_string());
+        config.set(SECTION, "height", &self.height.to_string());
+        config.set(SECTION, "fullscreen", &self.fullscreen.to_string());
+        config.set(SECTION, "integer_scale", &self.integer_scale.to_string());
+        config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let geometry = WindowGeometry {
+            position: Some((-20, 40)),
+            width: 800,
+            height: 400,
+            fullscreen: true,
+            integer_scale: true,
+        };
+        let config = Config::parse(&geometry.to_config().to_string());
+        let default = WindowGeometry::new(1280, 640);
+
+        assert_eq!(WindowGeometry::parse(&config, default), geometry);
+        assert_eq!(WindowGeometry::parse(&Config::default(), default), default);
+    }
+}
@@ -0,0 +1,133 @@
+use crate::capture::Image;
+use crate::capture::recorder::{AudioRecorder, Recorder};
+use crate::chip8::{CPU, debugger};
+use crate::display::palette::Palette;
+use crate::display::phosphor::{Persistence, Phosphor};
+use crate::rpl::RplStore;
+use std::io;
+
+/*
+ * Runs the CHIP-8 without a window, audio device or real time pacing
+ *
+ * Frames advance as fast as the host allows while keeping the same phosphor fade as
+ * the SDL front end, so recordings made here match what a player would see.
+ */
+pub struct Headless {
+    pub cpu: CPU,
+    pub palette: Palette,
+    pub instructions_per_frame: u32,
+    pub scale: u32,
+    phosphor: Phosphor,
+    recorder: Option<Recorder>,
+    audio_recorder: Option<AudioRecorder>,
+    rpl: Option<RplStore>,
+    frame: u64,
+}
+
+impl Headless {
+    pub fn new(cpu: CPU) -> Self {
+        Self {
+            cpu,
+            palette: Palette::default(),
+            instructions_per_frame: 10,
+            scale: 4,
+            phosphor: Phosphor::default(),
+            recorder: None,
+            audio_recorder: None,
+            rpl: None,
+            frame: 0,
+        }
+    }
+
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn phosphor(&self) -> &Phosphor {
+        &self.phosphor
+    }
+
+    pub fn set_persistence(&mut self, persistence: Persistence, present_on_draw: bool) {
+        self.phosphor.set_persistence(persistence);
+        self.phosphor.set_present_on_draw(present_on_draw);
+        self.cpu.set_present_on_draw(present_on_draw);
+    }
+
+    pub fn record(&mut self, recorder: Recorder) {
+        self.recorder = Some(recorder);
+    }
+
+    /*
+     * Audio is driven by the emulated timeline, one frame of samples per emulated frame
+     */
+    pub fn record_audio(&mut self, recorder: AudioRecorder) {
+        self.audio_recorder = Some(recorder);
+    }
+
+    pub fn set_rpl_store(&mut self, store: RplStore) {
+        self.rpl = Some(store);
+    }
+
+    /*
+     * Stop recording, returns the number of frames written
+     */
+    pub fn stop_recording(&mut self) -> io::Result<u64> {
+        if let Some(recorder) = self.audio_recorder.take() {
+            recorder.finish()?;
+        }
+
+        match self.recorder.take() {
+            Some(recorder) => recorder.finish(),
+            None => Ok(0),
+        }
+    }
+
+    pub fn image(&self) -> Image {
+        Image::from_colors(
+            &self.phosphor.colors(&self.palette),
+            self.phosphor.width() as u32,
+            self.phosphor.height() as u32,
+            self.scale,
+        )
+    }
+
+    pub fn run_frame(&mut self) -> io::Result<()> {
+        if let Err(e) = self.cpu.run_frame(self.instructions_per_frame) {
+            let backtrace = debugger::backtrace(&self.cpu.backtrace());
+            return Err(io::Error::other(format!("{}\n{}", e, backtrace)));
+        }
+        if let Some(rpl) = self.rpl.as_mut() {
+            rpl.sync(self.cpu.rpl_flags())?;
+        }
+        let changed = self.phosphor.update(self.cpu.take_picture());
+        self.frame += 1;
+
+        /*
+         * Frames the picture did not change on repeat the last one rather than build it again
+         */
+        if self
+            .recorder
+            .as_ref()
+            .is_some_and(|r| changed || r.frames() == 0)
+        {
+            let image = self.image();
+            if let Some(recorder) = self.recorder.as_mut() {
+                recorder.capture(&image)?;
+            }
+        } else if let Some(recorder) = self.recorder.as_mut() {
+            recorder.repeat()?;
+        }
+
+        if let Some(recorder) = self.audio_recorder.as_mut() {
+            recorder.frame(self.cpu.sound_edges(), self.cpu.sample_commands())?;
+        }
+        Ok(())
+    }
+
+    pub fn run(&mut self, frames: u64) -> io::Result<()> {
+        for _ in 0..frames {
+            self.run_frame()?;
+        }
+        Ok(())
+    }
+}
@@ -1,4 +1,9 @@
 pub mod audio;
+pub mod capture;
 pub mod chip8;
+pub mod config;
 pub mod display;
+pub mod headless;
 pub mod rom;
+pub mod rpl;
+pub mod vip;
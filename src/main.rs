@@ -1,14 +1,242 @@
+use chip_8::audio::BeeperConfig;
+use chip_8::capture::recorder::{AudioRecorder, RecordFormat, Recorder};
 use chip_8::chip8::CPU;
+use chip_8::chip8::font::Font;
+use chip_8::chip8::machine;
+use chip_8::config::Config;
+use chip_8::display::filter::Filter;
+use chip_8::display::input::{self, Input};
+use chip_8::display::palette::Palette;
+use chip_8::display::phosphor::Persistence;
+use chip_8::headless::Headless;
 use chip_8::rom;
-use sdl2::pixels::Color;
+use chip_8::rpl::RplStore;
+use chip_8::vip::{self, Vip};
+use std::env;
 use std::error::Error;
+use std::fs;
+use std::path::Path;
 
 fn main() -> Result<(), Box<dyn Error>> {
     let mut cpu = CPU::new();
     let program = rom::load_rom();
 
-    cpu.load_rom(&program);
-    let mut display = chip_8::display::Display::new(1280, 640, Color::GREEN)?;
+    let args: Vec<String> = env::args().collect();
+    let flag = |name: &str| {
+        args.iter()
+            .position(|arg| arg == name)
+            .and_then(|i| args.get(i + 1))
+    };
+
+    let config = Config::load()?;
+    let rom_hash = chip_8::rom::hash(&program);
+
+    /*
+     * --vip <interpreter image> runs the program on an emulated COSMAC VIP with the
+     * original interpreter, --vip-monitor <rom image> adds the monitor ROM and
+     * --vip-ram <bytes> sets the RAM size (4096 by default)
+     */
+    let vip_interpreter = flag("--vip")
+        .map(String::as_str)
+        .or(config.get(Some(&rom_hash), "vip_interpreter"));
+    if let Some(path) = vip_interpreter {
+        let monitor = flag("--vip-monitor")
+            .map(String::as_str)
+            .or(config.get(Some(&rom_hash), "vip_monitor"))
+            .map(fs::read)
+            .transpose()?;
+        let ram = flag("--vip-ram")
+            .map(String::as_str)
+            .or(config.get(Some(&rom_hash), "vip_ram"))
+            .map_or(Ok(4096), str::parse)?;
+
+        let mut vip = Vip::new(ram, monitor)?;
+        vip.load(vip::INTERPRETER_ADDRESS, &fs::read(path)?)?;
+        cpu.attach_vip(vip);
+    }
+
+    /*
+     * --platform hires|chip-8e|chip-8x runs a program written for one of the CHIP-8 variants
+     */
+    if let Some(platform) = flag("--platform")
+        .map(String::as_str)
+        .or(config.get(Some(&rom_hash), "platform"))
+    {
+        cpu.set_platform(platform.parse()?);
+    }
+
+    /*
+     * The platform's memory layout can be overridden per ROM with load_address,
+     * start_address, font_address, memory_size and stack_depth.
+     * --start-address <address> loads and starts the program there, e.g. 0x600 for the ETI-660
+     */
+    let mut machine = cpu.machine();
+    let address = |key: &str| {
+        config
+            .get(Some(&rom_hash), key)
+            .map(machine::parse_address)
+            .transpose()
+    };
+    if let Some(address) = address("load_address")? {
+        machine.load_address = address;
+        machine.start_address = address;
+    }
+    if let Some(address) = address("start_address")? {
+        machine.start_address = address;
+    }
+    if let Some(address) = flag("--start-address") {
+        machine.load_address = machine::parse_address(address)?;
+        machine.start_address = machine.load_address;
+    }
+    if let Some(address) = address("font_address")? {
+        machine.font_address = address;
+    }
+    if let Some(size) = config.get(Some(&rom_hash), "memory_size") {
+        machine.memory_size = machine::parse_number(size)?;
+    }
+    if let Some(depth) = config.get(Some(&rom_hash), "stack_depth") {
+        machine.stack_depth = machine::parse_number(depth)?;
+    }
+    if machine != cpu.machine() {
+        cpu.set_machine(machine)?;
+    }
+
+    /*
+     * --font <preset or file>, one of octo, vip, eti-660, dream6800, fishnchips and schip,
+     * or a file with the 80 byte small font optionally followed by a 100 byte big font
+     */
+    if let Some(font) = flag("--font")
+        .map(String::as_str)
+        .or(config.get(Some(&rom_hash), "font"))
+    {
+        let font = if Path::new(font).is_file() {
+            Font::from_bytes(font, &fs::read(font)?)?
+        } else {
+            font.parse()?
+        };
+        cpu.set_font(font);
+    }
+
+    cpu.load_rom(&program)?;
+
+    /*
+     * SCHIP programs keep high scores in the RPL flags, which are saved per ROM
+     */
+    let mut rpl = RplStore::for_rom(&rom_hash);
+    cpu.set_rpl_flags(rpl.load()?);
+
+    let palette = match config.get(Some(&rom_hash), "palette") {
+        Some(palette) => palette.parse::<Palette>()?,
+        None => Palette::default(),
+    };
+    let persistence = match config.get(Some(&rom_hash), "persistence") {
+        Some(persistence) => persistence.parse::<Persistence>()?,
+        None => Persistence::default(),
+    };
+    let present_on_draw = config.get(Some(&rom_hash), "present_on_draw") == Some("true");
+    /*
+     * --display-wait makes draws wait for the vertical blank like the COSMAC VIP
+     */
+    cpu.quirks.display_wait = config.get(Some(&rom_hash), "display_wait") == Some("true")
+        || env::args().any(|arg| arg == "--display-wait");
+    /*
+     * --key-release makes Fx0A wait for the key to be released like the COSMAC VIP
+     */
+    cpu.quirks.key_release = config.get(Some(&rom_hash), "key_release") == Some("true")
+        || env::args().any(|arg| arg == "--key-release");
+    /*
+     * --timing vip runs as many instructions per frame as the COSMAC VIP would
+     */
+    if let Some(timing) = config.get(Some(&rom_hash), "timing") {
+        cpu.timing = timing.parse()?;
+    }
+    if let Some(timing) = flag("--timing") {
+        cpu.timing = timing.parse()?;
+    }
+    let filter = match config.get(Some(&rom_hash), "filter") {
+        Some(filter) => filter.parse::<Filter>()?,
+        None => Filter::default(),
+    };
+
+    /*
+     * --waveform square|pulse:<duty>|triangle|sine|noise, --frequency <hz>, --volume <0-1>
+     * --attack <ms> and --release <ms> set how long the beeper fades in and out
+     */
+    let mut beeper = BeeperConfig::default();
+    if let Some(waveform) = flag("--waveform")
+        .map(String::as_str)
+        .or(config.get(Some(&rom_hash), "waveform"))
+    {
+        beeper.waveform = waveform.parse()?;
+    }
+    if let Some(frequency) = flag("--frequency")
+        .map(String::as_str)
+        .or(config.get(Some(&rom_hash), "frequency"))
+    {
+        beeper.frequency = frequency.parse()?;
+    }
+    if let Some(volume) = flag("--volume")
+        .map(String::as_str)
+        .or(config.get(Some(&rom_hash), "volume"))
+    {
+        beeper.volume = volume.parse::<f32>()?.clamp(0.0, 1.0);
+    }
+    if let Some(attack) = flag("--attack")
+        .map(String::as_str)
+        .or(config.get(Some(&rom_hash), "attack"))
+    {
+        beeper.attack = attack.parse::<f32>()?.max(0.0);
+    }
+    if let Some(release) = flag("--release")
+        .map(String::as_str)
+        .or(config.get(Some(&rom_hash), "release"))
+    {
+        beeper.release = release.parse::<f32>()?.max(0.0);
+    }
+
+    /*
+     * --headless <frames> runs without a window
+     * --record <path> captures every frame to a .gif, or numbered PNG/PPM files in a directory
+     * --record-audio <path> captures the beeper to a .wav
+     */
+    if let Some(frames) = flag("--headless") {
+        let mut headless = Headless::new(cpu);
+        headless.palette = palette;
+        headless.set_persistence(persistence, present_on_draw);
+        headless.set_rpl_store(rpl);
+        if let Some(path) = flag("--record") {
+            let format = RecordFormat::from_path(Path::new(path));
+            headless.record(Recorder::new(format, path)?);
+        }
+        if let Some(path) = flag("--record-audio") {
+            headless.record_audio(AudioRecorder::new(path, beeper)?);
+        }
+
+        headless.run(frames.parse()?)?;
+        let recorded = headless.stop_recording()?;
+        println!("Ran {} frames, recorded {}", headless.frame(), recorded);
+        return Ok(());
+    }
+
+    let mut display = chip_8::display::Display::new(1280, 640, palette)?;
+    display.set_beeper(beeper);
+    display.set_persistence(persistence, present_on_draw);
+    cpu.set_present_on_draw(present_on_draw);
+    display.set_filter(filter);
+
+    /*
+     * --input <profiles> picks the active input profiles, e.g. keyboard,numpad
+     * lets two players share the keyboard
+     */
+    let profiles = flag("--input")
+        .map(String::as_str)
+        .or(config.get(Some(&rom_hash), "input"))
+        .unwrap_or(input::DEFAULT_PROFILES);
+    let bindings = input::load_bindings()?;
+    let input = Input::load(&[&config, &bindings], Some(&rom_hash), profiles)?;
+    display.set_input(input, &rom_hash);
+    display.set_rpl_store(rpl);
+    display.message("ROM Loaded");
 
     display.run(&mut cpu)
 }
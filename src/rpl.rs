@@ -0,0 +1,93 @@
+use crate::chip8::RPL_FLAGS;
+use crate::config;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/*
+ * The RPL user flags of one ROM, kept between runs like the HP48 kept them
+ *
+ * Stored as raw bytes in rpl/<rom hash>.bin under the data directory. Nothing is
+ * written until the program changes the flags, so ROMs that never use Fx75 leave no file.
+ */
+#[derive(Debug, Clone)]
+pub struct RplStore {
+    path: PathBuf,
+    saved: [u8; RPL_FLAGS],
+}
+
+impl RplStore {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            saved: [0; RPL_FLAGS],
+        }
+    }
+
+    pub fn for_rom(rom_hash: &str) -> Self {
+        Self::new(
+            config::data_dir()
+                .join("rpl")
+                .join(format!("{}.bin", rom_hash)),
+        )
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /*
+     * The saved flags, all zero when the ROM has none yet
+     */
+    pub fn load(&mut self) -> io::Result<[u8; RPL_FLAGS]> {
+        match fs::read(&self.path) {
+            Ok(data) => {
+                let len = data.len().min(RPL_FLAGS);
+                self.saved = [0; RPL_FLAGS];
+                self.saved[..len].copy_from_slice(&data[..len]);
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        Ok(self.saved)
+    }
+
+    /*
+     * Called after each frame, the flags are written out if the program changed them
+     * since they were last loaded or saved
+     */
+    pub fn sync(&mut self, flags: &[u8; RPL_FLAGS]) -> io::Result<()> {
+        if *flags == self.saved {
+            return Ok(());
+        }
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.path, flags)?;
+        self.saved = *flags;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sync_and_load() {
+        let dir = std::env::temp_dir().join(format!("chip-8-rpl-{}", std::process::id()));
+        let path = dir.join("rom.bin");
+        let mut store = RplStore::new(&path);
+        assert_eq!(store.load().unwrap(), [0; RPL_FLAGS]);
+
+        store.sync(&[0; RPL_FLAGS]).unwrap();
+        assert!(!path.exists());
+
+        let mut flags = [0; RPL_FLAGS];
+        flags[..3].copy_from_slice(&[9, 9, 7]);
+        store.sync(&flags).unwrap();
+        assert_eq!(RplStore::new(&path).load().unwrap(), flags);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
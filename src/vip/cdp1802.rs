@@ -0,0 +1,446 @@
+/*
+ * RCA CDP1802 COSMAC microprocessor
+ *
+ * Sixteen 16-bit registers, any of which can be the program counter (selected by P)
+ * or the data pointer (selected by X). Instructions take 2 machine cycles, long
+ * branches and skips 3. Memory and I/O go through a `Bus` so the same core can be
+ * wired into different machines.
+ */
+pub trait Bus {
+    fn read(&mut self, address: u16) -> u8;
+    fn write(&mut self, address: u16, value: u8);
+    /*
+     * OUT 1-7, the byte on the data bus
+     */
+    fn output(&mut self, port: u8, value: u8);
+    /*
+     * INP 1-7, the byte the device puts on the data bus
+     */
+    fn input(&mut self, port: u8) -> u8;
+    /*
+     * External flags EF1-EF4
+     */
+    fn flag(&self, flag: u8) -> bool;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cdp1802 {
+    pub r: [u16; 16],
+    pub d: u8,
+    pub df: bool,
+    pub p: u8,
+    pub x: u8,
+    pub t: u8,
+    pub ie: bool,
+    pub q: bool,
+    /// IDL, waiting for an interrupt or DMA
+    pub idle: bool,
+}
+
+impl Default for Cdp1802 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Cdp1802 {
+    /*
+     * The state after a reset, R0 is the program counter and interrupts are enabled
+     */
+    pub fn new() -> Self {
+        Self {
+            r: [0; 16],
+            d: 0,
+            df: false,
+            p: 0,
+            x: 0,
+            t: 0,
+            ie: true,
+            q: false,
+            idle: false,
+        }
+    }
+
+    fn fetch(&mut self, bus: &mut impl Bus) -> u8 {
+        let pc = self.p as usize;
+        let byte = bus.read(self.r[pc]);
+        self.r[pc] = self.r[pc].wrapping_add(1);
+        byte
+    }
+
+    fn rx(&self) -> u16 {
+        self.r[self.x as usize]
+    }
+
+    fn set_low(&mut self, n: usize, value: u8) {
+        self.r[n] = (self.r[n] & 0xFF00) | value as u16;
+    }
+
+    fn set_high(&mut self, n: usize, value: u8) {
+        self.r[n] = (self.r[n] & 0x00FF) | (value as u16) << 8;
+    }
+
+    /*
+     * Respond to an interrupt request, returns the machine cycles used
+     */
+    pub fn interrupt(&mut self) -> u32 {
+        if !self.ie {
+            return 0;
+        }
+        self.t = self.x << 4 | self.p;
+        self.x = 2;
+        self.p = 1;
+        self.ie = false;
+        self.idle = false;
+        1
+    }
+
+    /*
+     * A DMA out cycle, the byte at R0 goes to the device and R0 moves on
+     */
+    pub fn dma_out(&mut self, bus: &mut impl Bus) -> u8 {
+        let byte = bus.read(self.r[0]);
+        self.r[0] = self.r[0].wrapping_add(1);
+        self.idle = false;
+        byte
+    }
+
+    fn add(&mut self, a: u8, b: u8, carry: bool) {
+        let sum = a as u16 + b as u16 + carry as u16;
+        self.d = sum as u8;
+        self.df = sum > 0xFF;
+    }
+
+    /*
+     * a - b, DF is set when there was no borrow
+     */
+    fn subtract(&mut self, a: u8, b: u8, borrow: bool) {
+        let difference = a as i16 - b as i16 - borrow as i16;
+        self.d = difference as u8;
+        self.df = difference >= 0;
+    }
+
+    fn short_branch(&mut self, bus: &mut impl Bus, condition: bool) {
+        let pc = self.p as usize;
+        let target = bus.read(self.r[pc]);
+        if condition {
+            self.set_low(pc, target);
+        } else {
+            self.r[pc] = self.r[pc].wrapping_add(1);
+        }
+    }
+
+    fn long_branch(&mut self, bus: &mut impl Bus, condition: bool) {
+        let pc = self.p as usize;
+        if condition {
+            let high = bus.read(self.r[pc]);
+            let low = bus.read(self.r[pc].wrapping_add(1));
+            self.r[pc] = (high as u16) << 8 | low as u16;
+        } else {
+            self.r[pc] = self.r[pc].wrapping_add(2);
+        }
+    }
+
+    fn long_skip(&mut self, condition: bool) {
+        let pc = self.p as usize;
+        if condition {
+            self.r[pc] = self.r[pc].wrapping_add(2);
+        }
+    }
+
+    /*
+     * Execute one instruction, returns the machine cycles used
+     */
+    pub fn step(&mut self, bus: &mut impl Bus) -> u32 {
+        if self.idle {
+            return 2;
+        }
+
+        let opcode = self.fetch(bus);
+        let i = opcode >> 4;
+        let n = (opcode & 0xF) as usize;
+
+        match i {
+            0x0 => {
+                if n == 0 {
+                    //IDL
+                    self.idle = true;
+                } else {
+                    //LDN
+                    self.d = bus.read(self.r[n]);
+                }
+            }
+            //INC
+            0x1 => self.r[n] = self.r[n].wrapping_add(1),
+            //DEC
+            0x2 => self.r[n] = self.r[n].wrapping_sub(1),
+            0x3 => {
+                let condition = match n {
+                    0x0 => true,
+                    0x1 => self.q,
+                    0x2 => self.d == 0,
+                    0x3 => self.df,
+                    0x4..=0x7 => bus.flag(n as u8 - 3),
+                    0x8 => false,
+                    0x9 => !self.q,
+                    0xA => self.d != 0,
+                    0xB => !self.df,
+                    _ => !bus.flag(n as u8 - 0xB),
+                };
+                //38 SKP skips the byte that would have been the branch target
+                self.short_branch(bus, condition);
+            }
+            0x4 => {
+                //LDA
+                self.d = bus.read(self.r[n]);
+                self.r[n] = self.r[n].wrapping_add(1);
+            }
+            //STR
+            0x5 => bus.write(self.r[n], self.d),
+            0x6 => match n {
+                //IRX
+                0x0 => self.r[self.x as usize] = self.rx().wrapping_add(1),
+                //OUT
+                0x1..=0x7 => {
+                    let value = bus.read(self.rx());
+                    self.r[self.x as usize] = self.rx().wrapping_add(1);
+                    bus.output(n as u8, value);
+                }
+                //undefined on the 1802
+                0x8 => {}
+                //INP
+                _ => {
+                    let value = bus.input(n as u8 - 8);
+                    bus.write(self.rx(), value);
+                    self.d = value;
+                }
+            },
+            0x7 => self.execute_7(bus, n),
+            //GLO, GHI, PLO, PHI
+            0x8 => self.d = self.r[n] as u8,
+            0x9 => self.d = (self.r[n] >> 8) as u8,
+            0xA => self.set_low(n, self.d),
+            0xB => self.set_high(n, self.d),
+            0xC => {
+                match n {
+                    0x0 => self.long_branch(bus, true),
+                    0x1 => self.long_branch(bus, self.q),
+                    0x2 => self.long_branch(bus, self.d == 0),
+                    0x3 => self.long_branch(bus, self.df),
+                    //NOP
+                    0x4 => {}
+                    0x5 => self.long_skip(!self.q),
+                    0x6 => self.long_skip(self.d != 0),
+                    0x7 => self.long_skip(!self.df),
+                    0x8 => self.long_skip(true),
+                    0x9 => self.long_branch(bus, !self.q),
+                    0xA => self.long_branch(bus, self.d != 0),
+                    0xB => self.long_branch(bus, !self.df),
+                    0xC => self.long_skip(self.ie),
+                    0xD => self.long_skip(self.q),
+                    0xE => self.long_skip(self.d == 0),
+                    _ => self.long_skip(self.df),
+                }
+                return 3;
+            }
+            //SEP, SEX
+            0xD => self.p = n as u8,
+            0xE => self.x = n as u8,
+            _ => self.execute_f(bus, n),
+        }
+        2
+    }
+
+    fn execute_7(&mut self, bus: &mut impl Bus, n: usize) {
+        match n {
+            //RET, DIS
+            0x0 | 0x1 => {
+                let value = bus.read(self.rx());
+                self.r[self.x as usize] = self.rx().wrapping_add(1);
+                self.x = value >> 4;
+                self.p = value & 0xF;
+                self.ie = n == 0;
+            }
+            //LDXA
+            0x2 => {
+                self.d = bus.read(self.rx());
+                self.r[self.x as usize] = self.rx().wrapping_add(1);
+            }
+            //STXD
+            0x3 => {
+                bus.write(self.rx(), self.d);
+                self.r[self.x as usize] = self.rx().wrapping_sub(1);
+            }
+            //ADC
+            0x4 => {
+                let m = bus.read(self.rx());
+                self.add(m, self.d, self.df);
+            }
+            //SDB
+            0x5 => {
+                let m = bus.read(self.rx());
+                self.subtract(m, self.d, !self.df);
+            }
+            //SHRC
+            0x6 => {
+                let carry = self.d & 1 == 1;
+                self.d = self.d >> 1 | (self.df as u8) << 7;
+                self.df = carry;
+            }
+            //SMB
+            0x7 => {
+                let m = bus.read(self.rx());
+                self.subtract(self.d, m, !self.df);
+            }
+            //SAV
+            0x8 => bus.write(self.rx(), self.t),
+            //MARK
+            0x9 => {
+                self.t = self.x << 4 | self.p;
+                bus.write(self.r[2], self.t);
+                self.x = self.p;
+                self.r[2] = self.r[2].wrapping_sub(1);
+            }
+            //REQ, SEQ
+            0xA => self.q = false,
+            0xB => self.q = true,
+            //ADCI
+            0xC => {
+                let m = self.fetch(bus);
+                self.add(m, self.d, self.df);
+            }
+            //SDBI
+            0xD => {
+                let m = self.fetch(bus);
+                self.subtract(m, self.d, !self.df);
+            }
+            //SHLC
+            0xE => {
+                let carry = self.d & 0x80 != 0;
+                self.d = self.d << 1 | self.df as u8;
+                self.df = carry;
+            }
+            //SMBI
+            _ => {
+                let m = self.fetch(bus);
+                self.subtract(self.d, m, !self.df);
+            }
+        }
+    }
+
+    /*
+     * F0-F7 take their operand from M(R(X)), F8-FF from the byte after the instruction
+     */
+    fn execute_f(&mut self, bus: &mut impl Bus, n: usize) {
+        let shift = n & 7 == 6;
+        let operand = match (n, shift) {
+            (_, true) => 0,
+            (0x0..=0x7, false) => bus.read(self.rx()),
+            _ => self.fetch(bus),
+        };
+
+        match n & 7 {
+            //LDX, LDI
+            0x0 => self.d = operand,
+            //OR, ORI
+            0x1 => self.d |= operand,
+            //AND, ANI
+            0x2 => self.d &= operand,
+            //XOR, XRI
+            0x3 => self.d ^= operand,
+            //ADD, ADI
+            0x4 => self.add(operand, self.d, false),
+            //SD, SDI
+            0x5 => self.subtract(operand, self.d, false),
+            //SHR, SHL
+            0x6 => {
+                if n == 0x6 {
+                    self.df = self.d & 1 == 1;
+                    self.d >>= 1;
+                } else {
+                    self.df = self.d & 0x80 != 0;
+                    self.d <<= 1;
+                }
+            }
+            //SM, SMI
+            _ => self.subtract(self.d, operand, false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Memory([u8; 256]);
+
+    impl Bus for Memory {
+        fn read(&mut self, address: u16) -> u8 {
+            self.0[address as usize & 0xFF]
+        }
+        fn write(&mut self, address: u16, value: u8) {
+            self.0[address as usize & 0xFF] = value;
+        }
+        fn output(&mut self, _: u8, _: u8) {}
+        fn input(&mut self, _: u8) -> u8 {
+            0
+        }
+        fn flag(&self, _: u8) -> bool {
+            false
+        }
+    }
+
+    fn run(program: &[u8], steps: usize) -> (Cdp1802, Memory, u32) {
+        let mut memory = Memory([0; 256]);
+        memory.0[..program.len()].copy_from_slice(program);
+        let mut cpu = Cdp1802::new();
+        let cycles = (0..steps).map(|_| cpu.step(&mut memory)).sum();
+        (cpu, memory, cycles)
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        //LDI 80, PLO R3, SEX 3, LDI F0, ADD, STR R3, SMI 71
+        let (cpu, memory, cycles) = run(
+            &[0xF8, 0x80, 0xA3, 0xE3, 0xF8, 0xF0, 0xF4, 0x53, 0xFF, 0x71],
+            7,
+        );
+        assert_eq!(memory.0[0x80], 0xF0);
+        assert_eq!((cpu.d, cpu.df), (0x7F, true));
+        assert_eq!(cycles, 14);
+    }
+
+    #[test]
+    fn test_branches() {
+        //LDI 00, BZ 06, LDI 01, (06) LBR 000A, SEQ, (0A) SHL, LSZ, SEQ, SEQ
+        let (cpu, _, cycles) = run(
+            &[
+                0xF8, 0x00, 0x32, 0x06, 0xF8, 0x01, 0xC0, 0x00, 0x0A, 0x7B, 0xFE, 0xCE, 0x7B, 0x7B,
+            ],
+            5,
+        );
+        assert_eq!((cpu.r[0], cpu.d, cpu.q), (0x0E, 0, false));
+        assert_eq!(cycles, 2 + 2 + 3 + 2 + 3);
+    }
+
+    #[test]
+    fn test_mark_and_return() {
+        //R2 = 0x80, SEX 2, MARK then RET with X and P restored from the stack
+        let mut memory = Memory([0; 256]);
+        memory.0[..5].copy_from_slice(&[0xF8, 0x80, 0xA2, 0x79, 0x12]);
+        let mut cpu = Cdp1802::new();
+        cpu.x = 5;
+        for _ in 0..3 {
+            cpu.step(&mut memory);
+        }
+        assert_eq!((memory.0[0x80], cpu.r[2], cpu.x), (0x50, 0x7F, 0));
+
+        //INC R2, SEX 2, RET
+        cpu.r[0] = 0x10;
+        memory.0[0x10..0x13].copy_from_slice(&[0x12, 0xE2, 0x70]);
+        for _ in 0..3 {
+            cpu.step(&mut memory);
+        }
+        assert_eq!((cpu.x, cpu.p, cpu.ie, cpu.r[2]), (5, 0, true, 0x81));
+    }
+}
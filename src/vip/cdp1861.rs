@@ -0,0 +1,97 @@
+/*
+ * RCA CDP1861 "Pixie" video display controller
+ *
+ * Every frame is 262 lines of 14 machine cycles. Two lines before the 128 visible
+ * lines it interrupts the CPU, then during each visible line it takes 8 bytes from
+ * memory by DMA and shows them as 64 pixels. EF1 is raised for the 4 lines before
+ * the display starts and the last 4 lines of it, so software can find its place.
+ */
+pub const LINES: u32 = 262;
+pub const CYCLES_PER_LINE: u32 = 14;
+pub const FRAME_CYCLES: u32 = LINES * CYCLES_PER_LINE;
+
+pub const WIDTH: usize = 64;
+pub const HEIGHT: usize = 128;
+
+pub const INTERRUPT_LINE: u32 = 78;
+pub const FIRST_LINE: u32 = 80;
+pub const LAST_LINE: u32 = FIRST_LINE + HEIGHT as u32;
+/*
+ * The CPU runs this many cycles of a visible line before the DMA burst
+ */
+pub const DMA_START: u32 = 6;
+pub const DMA_BYTES: usize = WIDTH / 8;
+
+#[derive(Debug, Clone)]
+pub struct Cdp1861 {
+    enabled: bool,
+    line: u32,
+    pixels: Vec<bool>,
+}
+
+impl Default for Cdp1861 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Cdp1861 {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            line: 0,
+            pixels: vec![false; WIDTH * HEIGHT],
+        }
+    }
+
+    /*
+     * INP 1 turns the display on, OUT 1 turns it off
+     */
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.pixels.fill(false);
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_line(&mut self, line: u32) {
+        self.line = line;
+    }
+
+    pub fn line(&self) -> u32 {
+        self.line
+    }
+
+    pub fn interrupt(&self) -> bool {
+        self.enabled && (INTERRUPT_LINE..FIRST_LINE).contains(&self.line)
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.enabled && (FIRST_LINE..LAST_LINE).contains(&self.line)
+    }
+
+    pub fn ef1(&self) -> bool {
+        self.enabled
+            && ((FIRST_LINE - 4..FIRST_LINE).contains(&self.line)
+                || (LAST_LINE - 4..LAST_LINE).contains(&self.line))
+    }
+
+    /*
+     * The bytes fetched by DMA for the current line
+     */
+    pub fn scan(&mut self, bytes: &[u8; DMA_BYTES]) {
+        let row = (self.line - FIRST_LINE) as usize;
+        let line = &mut self.pixels[row * WIDTH..(row + 1) * WIDTH];
+        for (i, pixel) in line.iter_mut().enumerate() {
+            *pixel = bytes[i / 8] >> (7 - i % 8) & 1 == 1;
+        }
+    }
+
+    pub fn pixels(&self) -> &[bool] {
+        &self.pixels
+    }
+}
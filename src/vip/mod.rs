@@ -0,0 +1,270 @@
+use crate::chip8::SoundEdge;
+use crate::vip::cdp1802::{Bus, Cdp1802};
+use crate::vip::cdp1861::Cdp1861;
+use std::error::Error;
+
+pub mod cdp1802;
+pub mod cdp1861;
+
+/*
+ * RCA COSMAC VIP
+ *
+ * A CDP1802 with RAM at the bottom of memory, the 512 byte monitor ROM at 0x8000 and
+ * a CDP1861 for video. Instead of interpreting CHIP-8 directly, the original CHIP-8
+ * interpreter is loaded at 0x0000 and runs as 1802 machine code, so 0nnn calls into
+ * machine code routines work like they did on the real machine.
+ *
+ * Neither the monitor nor the interpreter are included, they are loaded from images.
+ * On reset the ROM also appears at 0x0000 until the first access above 0x8000, which
+ * is how the monitor gets to run before handing over to the program in RAM.
+ *
+ * I/O: INP 1 and OUT 1 switch the display on and off, OUT 2 latches the key to test
+ * and EF3 reports whether it is held. Q drives the beeper.
+ */
+pub const INTERPRETER_ADDRESS: u16 = 0x0000;
+pub const PROGRAM_ADDRESS: u16 = 0x0200;
+pub const MONITOR_ADDRESS: u16 = 0x8000;
+pub const MONITOR_SIZE: usize = 512;
+
+struct VipBus {
+    ram: Vec<u8>,
+    monitor: Option<Vec<u8>>,
+    monitor_at_zero: bool,
+    video: Cdp1861,
+    key_latch: u8,
+    keypad: [bool; 16],
+}
+
+impl Bus for VipBus {
+    fn read(&mut self, address: u16) -> u8 {
+        if address & MONITOR_ADDRESS != 0 {
+            self.monitor_at_zero = false;
+        }
+
+        match &self.monitor {
+            Some(monitor) if address & MONITOR_ADDRESS != 0 || self.monitor_at_zero => {
+                monitor[address as usize % MONITOR_SIZE]
+            }
+            _ if address & MONITOR_ADDRESS != 0 => 0xFF,
+            _ => self.ram[address as usize % self.ram.len()],
+        }
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        if address & MONITOR_ADDRESS == 0 {
+            let len = self.ram.len();
+            self.ram[address as usize % len] = value;
+        }
+    }
+
+    fn output(&mut self, port: u8, value: u8) {
+        match port {
+            1 => self.video.set_enabled(false),
+            2 => self.key_latch = value & 0xF,
+            _ => {}
+        }
+    }
+
+    fn input(&mut self, port: u8) -> u8 {
+        if port == 1 {
+            self.video.set_enabled(true);
+        }
+        0xFF
+    }
+
+    fn flag(&self, flag: u8) -> bool {
+        match flag {
+            1 => self.video.ef1(),
+            3 => self.keypad[self.key_latch as usize],
+            _ => false,
+        }
+    }
+}
+
+pub struct Vip {
+    pub cpu: Cdp1802,
+    bus: VipBus,
+    cycle: u32,
+    sound_edges: Vec<SoundEdge>,
+}
+
+impl Vip {
+    /*
+     * `ram_size` is 2048 to 32768 bytes, the VIP came with 2K and took up to 4K on the board
+     */
+    pub fn new(ram_size: usize, monitor: Option<Vec<u8>>) -> Result<Self, Box<dyn Error>> {
+        if !(2048..=32768).contains(&ram_size) || !ram_size.is_power_of_two() {
+            return Err(format!("Unsupported VIP RAM size: {}", ram_size).into());
+        }
+        if let Some(monitor) = &monitor
+            && monitor.len() != MONITOR_SIZE
+        {
+            return Err(format!("The VIP monitor ROM must be {} bytes", MONITOR_SIZE).into());
+        }
+
+        Ok(Self {
+            cpu: Cdp1802::new(),
+            bus: VipBus {
+                ram: vec![0; ram_size],
+                monitor_at_zero: monitor.is_some(),
+                monitor,
+                video: Cdp1861::new(),
+                key_latch: 0,
+                keypad: [false; 16],
+            },
+            cycle: 0,
+            sound_edges: Vec::new(),
+        })
+    }
+
+    /*
+     * Copy `data` into RAM, e.g. the interpreter at 0x0000 and a CHIP-8 program at 0x0200
+     */
+    pub fn load(&mut self, address: u16, data: &[u8]) -> Result<(), Box<dyn Error>> {
+        let start = address as usize;
+        let end = start + data.len();
+        if end > self.bus.ram.len() {
+            return Err(format!(
+                "{} bytes at 0x{:04X} do not fit in {} bytes of RAM",
+                data.len(),
+                address,
+                self.bus.ram.len()
+            )
+            .into());
+        }
+        self.bus.ram[start..end].copy_from_slice(data);
+        Ok(())
+    }
+
+    pub fn ram(&self) -> &[u8] {
+        &self.bus.ram
+    }
+
+    pub fn set_keypad(&mut self, keypad: &[bool; 16]) {
+        self.bus.keypad = *keypad;
+    }
+
+    /*
+     * 64x128, the CHIP-8 interpreter repeats every row on 4 lines
+     */
+    pub fn pixels(&self) -> &[bool] {
+        self.bus.video.pixels()
+    }
+
+    pub fn sound_edges(&self) -> &[SoundEdge] {
+        &self.sound_edges
+    }
+
+    /*
+     * One frame of the CDP1861, line by line
+     * an instruction running past the end of a line finishes first, the overrun
+     * comes out of the next line, and likewise for the frame
+     */
+    pub fn run_frame(&mut self) {
+        self.sound_edges.clear();
+
+        for line in 0..cdp1861::LINES {
+            self.bus.video.set_line(line);
+            let start = line * cdp1861::CYCLES_PER_LINE;
+
+            if self.bus.video.is_visible() {
+                self.run_until(start + cdp1861::DMA_START);
+                let mut bytes = [0; cdp1861::DMA_BYTES];
+                for byte in bytes.iter_mut() {
+                    *byte = self.cpu.dma_out(&mut self.bus);
+                }
+                self.bus.video.scan(&bytes);
+                self.cycle += cdp1861::DMA_BYTES as u32;
+            }
+
+            self.run_until(start + cdp1861::CYCLES_PER_LINE);
+        }
+
+        self.cycle -= cdp1861::FRAME_CYCLES;
+    }
+
+    fn run_until(&mut self, cycle: u32) {
+        while self.cycle < cycle {
+            if self.bus.video.interrupt() && self.cpu.ie {
+                self.cycle += self.cpu.interrupt();
+            }
+
+            let q = self.cpu.q;
+            self.cycle += self.cpu.step(&mut self.bus);
+            if self.cpu.q != q {
+                self.sound_edges.push(SoundEdge {
+                    position: (self.cycle as f32 / cdp1861::FRAME_CYCLES as f32).min(1.0),
+                    on: self.cpu.q,
+                });
+            }
+        }
+    }
+
+    /*
+     * Where the CHIP-8 interpreter keeps its state, for the register display
+     * V0-VF sit just below the display page, I is RA, the program counter R5
+     * and the delay and sound timers the two halves of R8
+     */
+    pub fn chip8_registers(&self) -> &[u8; 16] {
+        let start = self.bus.ram.len() - 0x110;
+        self.bus.ram[start..start + 16].try_into().unwrap()
+    }
+
+    pub fn chip8_index(&self) -> u16 {
+        self.cpu.r[0xA]
+    }
+
+    pub fn chip8_pc(&self) -> u16 {
+        self.cpu.r[5]
+    }
+
+    pub fn chip8_delay_timer(&self) -> u8 {
+        (self.cpu.r[8] >> 8) as u8
+    }
+
+    pub fn chip8_sound_timer(&self) -> u8 {
+        self.cpu.r[8] as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_dma() {
+        //R0 becomes the DMA pointer, so move the program counter to R3 first
+        let program = [
+            0xF8, 0x00, 0xB3, 0xF8, 0x07, 0xA3, 0xD3, // R3 = 0x0007, SEP 3
+            0xE2, // SEX 2
+            0xF8, 0x0F, 0xB2, 0xF8, 0xF0, 0xA2, // R2 = 0x0FF0
+            0xF8, 0x00, 0xB1, 0xF8, 0x41, 0xA1, // R1 = 0x0041
+            0x69, // INP 1, display on
+            0x30, 0x15, // BR 15
+        ];
+        //interrupt: save T, point R0 at 0x0100, wait out EF1 so the request is over,
+        //then return through the RET before the entry
+        let interrupt = [
+            0x70, // RET
+            0x22, 0x78, // DEC R2, SAV
+            0xF8, 0x01, 0xB0, 0xF8, 0x00, 0xA0, // R0 = 0x0100
+            0x34, 0x49, // B1 49
+            0x30, 0x40, // BR 40
+        ];
+
+        let mut vip = Vip::new(4096, None).unwrap();
+        vip.load(0, &program).unwrap();
+        vip.load(0x40, &interrupt).unwrap();
+        vip.load(0x100, &[0xFF]).unwrap();
+        vip.load(0x108, &[0x80]).unwrap();
+
+        vip.run_frame();
+        vip.run_frame();
+
+        let pixels = vip.pixels();
+        assert!(pixels[..8].iter().all(|p| *p));
+        assert!(!pixels[8]);
+        assert!(pixels[64] && !pixels[65]);
+        assert_eq!((vip.cpu.p, vip.cpu.r[0x2]), (3, 0x0FF0));
+    }
+}